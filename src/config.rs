@@ -1,9 +1,22 @@
 //! Application configuration and color parsing.
 
+use crate::custom_shape::CustomShape;
+use crate::keymap::KeyBinding;
+use crate::persistence::Point;
 use eframe::egui;
 use facet::Facet;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Config file read from the working directory.
+pub const CONFIG_FILE: &str = "config.toml";
+
+/// How often the config file is checked for changes.
+pub const RELOAD_INTERVAL: Duration = Duration::from_millis(500);
 
 #[derive(Facet, Clone)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct Config {
     #[facet(default = "#FFFFFF".to_string())]
     pub bg_color: String,
@@ -15,38 +28,538 @@ pub struct Config {
     pub selection_box_color: String,
     #[facet(default = true)]
     pub grid_enabled: bool,
+    /// Draw the document's reference image, if it has one.
+    #[facet(default = true)]
+    pub show_background: bool,
     #[facet(default = 40.0)]
     pub grid_spacing: f32,
     #[facet(default = "#CCCCCC".to_string())]
     pub grid_color: String,
+    /// Layout of the grid lines, which points snap to.
+    #[facet(default)]
+    pub grid_type: GridType,
+    /// Number of spokes in a polar grid, whose rings are `grid_spacing` apart.
+    #[facet(default = 12)]
+    pub polar_spokes: u32,
+    /// What snapping to the grid lines up: point edges, or point centres with corners or cells.
+    #[facet(default)]
+    pub snap_mode: SnapMode,
+    /// Every this many grid lines is a major line, drawn in `grid_major_color`; 0 or 1 for none.
+    #[facet(default = 5)]
+    pub grid_major_every: u32,
+    #[facet(default = "#999999".to_string())]
+    pub grid_major_color: String,
+    /// Width of major grid lines in screen points; minor lines are 1 point wide.
+    #[facet(default = 1.5)]
+    pub grid_major_width: f32,
+    /// Minor grid lines closer than this many screen points are thinned out when zoomed out.
+    #[facet(default = 8.0)]
+    pub grid_min_spacing: f32,
+    /// Draw the x and y axes through the canvas origin.
+    #[facet(default = true)]
+    pub show_axes: bool,
+    #[facet(default = "#666666".to_string())]
+    pub axis_color: String,
+    /// Overview of the whole document in the canvas's bottom right corner.
+    #[facet(default = true)]
+    pub show_minimap: bool,
+    /// Width of the minimap in screen points; its height is two thirds of this.
+    #[facet(default = 180.0)]
+    pub minimap_width: f32,
+    /// Rulers along the top and left of the canvas, to drag guides out of.
+    #[facet(default = true)]
+    pub show_rulers: bool,
+    /// Snap dragged points to the ruler guides.
+    #[facet(default = true)]
+    pub snap_to_guides: bool,
+    #[facet(default = "#00AAFF".to_string())]
+    pub ruler_guide_color: String,
+    #[facet(default = "#00AA00".to_string())]
+    pub measure_color: String,
     #[facet(default = 20.0)]
     pub point_radius: f32,
     #[facet(default = 1.0)]
     pub move_step: f32,
     #[facet(default = 20.0)]
     pub move_step_large: f32,
+    /// Whether points are filled with `point_color`; off draws just their outlines.
+    #[facet(default = true)]
+    pub point_fill: bool,
+    /// Width of the outline drawn around each point, or 0 for none.
+    #[facet(default = 1.0)]
+    pub point_stroke_width: f32,
+    /// Outlines contrast with the fill so that overlapping points stay distinguishable.
+    #[facet(default = "#FFFFFF".to_string())]
+    pub point_stroke_color: String,
+    /// Width of the `selected_color` halo drawn around selected points.
+    #[facet(default = 3.0)]
+    pub selected_halo_width: f32,
+    #[facet(default = "#888888".to_string())]
+    pub edge_color: String,
+    #[facet(default = 2.0)]
+    pub edge_width: f32,
+    #[facet(default = "#3366CC".to_string())]
+    pub path_color: String,
+    #[facet(default = 2.0)]
+    pub path_width: f32,
+    /// Opacity of a closed path's fill, from 0 (none) to 1.
+    #[facet(default = 0.25)]
+    pub path_fill_opacity: f32,
+    #[facet(default = 14.0)]
+    pub label_font_size: f32,
+    #[facet(default = "#333333".to_string())]
+    pub label_color: String,
+    /// Which side of a point its label is drawn on.
+    #[facet(default)]
+    pub label_anchor: LabelAnchor,
+    /// Show each point's id beside it, for debugging.
+    #[facet(default)]
+    pub show_ids: bool,
+    /// How close, in canvas units, a dragged point must come to line up with another before it snaps.
+    #[facet(default = 8.0)]
+    pub snap_distance: f32,
+    #[facet(default = "#FF00FF".to_string())]
+    pub guide_color: String,
+    /// Points on the same layer closer than this are duplicates, to highlight or merge.
+    #[facet(default = 1.0)]
+    pub duplicate_tolerance: f32,
+    #[facet(default = "#FF8800".to_string())]
+    pub duplicate_color: String,
+    /// Mark sets of duplicate points on the canvas.
+    #[facet(default)]
+    pub show_duplicates: bool,
+    /// Overrides for the default key bindings, see [`crate::keymap`].
+    #[facet(default)]
+    pub keymap: Vec<KeyBinding>,
+    /// Macro registers, each a comma-separated list of steps such as `"3 clone-down, move-left"`.
+    #[facet(default)]
+    pub macros: BTreeMap<String, String>,
+    /// Named glyphs usable as `PointShape::Custom`, see [`CustomShape`].
+    #[facet(default)]
+    pub custom_shapes: BTreeMap<String, CustomShape>,
 }
 
-impl Config {
+/// Just the `[macros]` table, for rewriting that part of the config file.
+#[derive(Facet)]
+struct MacrosTable {
+    macros: BTreeMap<String, String>,
+}
+
+/// Where a label sits relative to its point, written in lowercase in the config.
+#[derive(Facet, Clone, Copy, Default, PartialEq)]
+#[repr(u8)]
+#[facet(rename_all = "snake_case")]
+pub enum LabelAnchor {
+    #[default]
+    Right,
+    Left,
+    Above,
+    Below,
+    Center,
+}
+
+/// The grid's layout, written in lowercase in the config.
+#[derive(Facet, Clone, Copy, Default, PartialEq)]
+#[repr(u8)]
+#[facet(rename_all = "snake_case")]
+pub enum GridType {
+    /// Squares `grid_spacing` wide.
+    #[default]
+    Square,
+    /// Equilateral triangles with sides `grid_spacing` long.
+    Isometric,
+    /// Hexagons with centres `grid_spacing` apart.
+    Hex,
+    /// Rings `grid_spacing` apart round the origin, crossed by `polar_spokes` spokes.
+    Polar,
+}
+
+/// How points snap to the grid, written in lowercase in the config.
+#[derive(Facet, Clone, Copy, Default, PartialEq)]
+#[repr(u8)]
+#[facet(rename_all = "snake_case")]
+pub enum SnapMode {
+    /// The nearest edges of a point's bounds on square grids; its centre to the nearest
+    /// grid node on the others, as for `Corner` except on hex grids, where it is `Center`.
+    #[default]
+    Edge,
+    /// The point's centre on the nearest grid corner: where lines cross, or a hexagon vertex.
+    Corner,
+    /// The point's centre in the middle of the nearest grid cell.
+    Center,
+}
+
+impl SnapMode {
+    /// The next mode along, wrapping round.
+    #[must_use]
+    pub fn next(self) -> Self {
+        match self {
+            SnapMode::Edge => SnapMode::Corner,
+            SnapMode::Corner => SnapMode::Center,
+            SnapMode::Center => SnapMode::Edge,
+        }
+    }
+
     #[must_use]
-    /// # Panics
+    pub fn description(self) -> &'static str {
+        match self {
+            SnapMode::Edge => "edges",
+            SnapMode::Corner => "corners",
+            SnapMode::Center => "centres",
+        }
+    }
+}
+
+/// A display setting flipped from the keyboard, which then outlasts config reloads.
+#[derive(Clone, Copy, PartialEq)]
+pub enum ViewSetting {
+    Grid,
+    Axes,
+    Rulers,
+    Minimap,
+    Background,
+    Ids,
+    Duplicates,
+    SnapMode,
+}
+
+/// A point's effective appearance, after applying its own style over the config's.
+pub struct Style {
+    pub fill: bool,
+    pub stroke_width: f32,
+    pub stroke_color: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        facet_toml::from_str::<Config>("").expect("every config field has a default")
+    }
+}
+
+impl Config {
+    /// Load `config.toml` once, falling back to the defaults if it is missing or invalid.
     ///
-    /// May panic if the config TOML is invalid TODO (fix)
+    /// Only `--keys` uses this; the app reads the file through a [`ConfigWatcher`] so it can
+    /// report errors and pick up edits.
+    #[must_use]
     pub fn load() -> Self {
-        if let Ok(contents) = std::fs::read_to_string("config.toml") {
-            if let Ok(config) = facet_toml::from_str::<Config>(&contents) {
-                return config;
+        Self::read(CONFIG_FILE).unwrap_or_default()
+    }
+
+    /// Parse the config file at `path`, using the defaults if it does not exist.
+    ///
+    /// # Errors
+    ///
+    /// Returns a message describing the problem if the file can't be read or parsed, or if
+    /// any colour is invalid, e.g. half typed.
+    pub fn read(path: &str) -> Result<Self, String> {
+        let config = match std::fs::read_to_string(path) {
+            Ok(contents) => {
+                facet_toml::from_str::<Config>(&contents).map_err(|e| format!("{path}: {e}"))?
             }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(e) => return Err(format!("{path}: {e}")),
+        };
+        let errors = config.colour_errors();
+        if errors.is_empty() {
+            Ok(config)
+        } else {
+            Err(format!("{path}: {}", errors.join("; ")))
+        }
+    }
+
+    /// A hand-edited stroke colour that isn't `#RRGGBB` falls back to `point_stroke_color`.
+    #[must_use]
+    pub fn style_for(&self, pt: &Point) -> Style {
+        Style {
+            fill: pt.style.fill.unwrap_or(self.point_fill),
+            stroke_width: pt.style.stroke_width.unwrap_or(self.point_stroke_width),
+            stroke_color: pt
+                .style
+                .stroke_color
+                .clone()
+                .filter(|hex| Self::parse_colour(hex).is_some())
+                .unwrap_or_else(|| self.point_stroke_color.clone()),
+        }
+    }
+
+    /// Outline of the named custom shape in unit coordinates, if it is defined and valid.
+    #[must_use]
+    pub fn custom_outline(&self, name: &str) -> Option<Vec<egui::Vec2>> {
+        self.custom_shapes.get(name)?.outline().ok()
+    }
+
+    /// Take `setting` from `from`, e.g. the config in use before a reload.
+    pub fn copy_setting(&mut self, from: &Config, setting: ViewSetting) {
+        match setting {
+            ViewSetting::Grid => self.grid_enabled = from.grid_enabled,
+            ViewSetting::Axes => self.show_axes = from.show_axes,
+            ViewSetting::Rulers => self.show_rulers = from.show_rulers,
+            ViewSetting::Minimap => self.show_minimap = from.show_minimap,
+            ViewSetting::Background => self.show_background = from.show_background,
+            ViewSetting::Ids => self.show_ids = from.show_ids,
+            ViewSetting::Duplicates => self.show_duplicates = from.show_duplicates,
+            ViewSetting::SnapMode => self.snap_mode = from.snap_mode,
         }
-        facet_toml::from_str::<Config>("").unwrap()
     }
 
+    /// Problems with the `custom_shapes` definitions, one message per invalid shape.
     #[must_use]
-    pub fn parse_colour(hex: &str) -> egui::Color32 {
-        let hex = hex.trim_start_matches('#');
-        let r = u8::from_str_radix(&hex[0..2], 16).unwrap_or(0);
-        let g = u8::from_str_radix(&hex[2..4], 16).unwrap_or(0);
-        let b = u8::from_str_radix(&hex[4..6], 16).unwrap_or(0);
-        egui::Color32::from_rgb(r, g, b)
+    pub fn custom_shape_errors(&self) -> Vec<String> {
+        self.custom_shapes
+            .iter()
+            .filter_map(|(name, shape)| {
+                shape
+                    .outline()
+                    .err()
+                    .map(|e| format!("custom_shapes.{name}: {e}"))
+            })
+            .collect()
+    }
+
+    /// Replace the `[macros]` table in the config file at `path`, leaving the rest untouched.
+    ///
+    /// # Errors
+    ///
+    /// Returns a message describing the problem if the file can't be read or written, or if it
+    /// defines macros some other way than a `[macros]` table (which it can't rewrite safely).
+    pub fn write_macros(path: &str, macros: &BTreeMap<String, String>) -> Result<(), String> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(format!("{path}: {e}")),
+        };
+        let table = facet_toml::to_string(&MacrosTable {
+            macros: macros.clone(),
+        })
+        .map_err(|e| format!("{path}: {e}"))?;
+        let updated =
+            replace_macros_table(&contents, &table).map_err(|e| format!("{path}: {e}"))?;
+        std::fs::write(path, updated).map_err(|e| format!("{path}: {e}"))
+    }
+
+    /// Parse a `#RRGGBB` colour, or `None` if it isn't one.
+    #[must_use]
+    pub fn parse_colour(hex: &str) -> Option<egui::Color32> {
+        let hex = hex.strip_prefix('#').unwrap_or(hex);
+        if hex.len() != 6 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+        Some(egui::Color32::from_rgb(
+            channel(0)?,
+            channel(2)?,
+            channel(4)?,
+        ))
+    }
+
+    /// A colour for drawing, already checked by [`Config::read`] or when loading the document;
+    /// anything invalid that slips through is drawn black rather than failing.
+    #[must_use]
+    pub fn colour(hex: &str) -> egui::Color32 {
+        Self::parse_colour(hex).unwrap_or(egui::Color32::BLACK)
+    }
+
+    /// Every colour setting, by key.
+    fn colours(&self) -> [(&'static str, &str); 15] {
+        [
+            ("bg_color", &self.bg_color),
+            ("point_color", &self.point_color),
+            ("selected_color", &self.selected_color),
+            ("selection_box_color", &self.selection_box_color),
+            ("grid_color", &self.grid_color),
+            ("grid_major_color", &self.grid_major_color),
+            ("axis_color", &self.axis_color),
+            ("ruler_guide_color", &self.ruler_guide_color),
+            ("measure_color", &self.measure_color),
+            ("point_stroke_color", &self.point_stroke_color),
+            ("edge_color", &self.edge_color),
+            ("path_color", &self.path_color),
+            ("label_color", &self.label_color),
+            ("guide_color", &self.guide_color),
+            ("duplicate_color", &self.duplicate_color),
+        ]
+    }
+
+    /// Problems with the colour settings, one message per colour that isn't `#RRGGBB`.
+    #[must_use]
+    pub fn colour_errors(&self) -> Vec<String> {
+        self.colours()
+            .into_iter()
+            .filter(|(_, hex)| Self::parse_colour(hex).is_none())
+            .map(|(key, hex)| format!("{key}: {hex:?} is not a #RRGGBB colour"))
+            .collect()
+    }
+}
+
+/// Polls the config file's modification time so edits apply without a restart.
+pub struct ConfigWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    last_check: Option<Instant>,
+}
+
+impl ConfigWatcher {
+    #[must_use]
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: PathBuf::from(path),
+            modified: None,
+            last_check: None,
+        }
+    }
+
+    /// Re-parse the config if the file changed since the last poll.
+    ///
+    /// Returns `None` when nothing changed (or it is too soon to check again),
+    /// otherwise the freshly parsed config or the parse error. A deleted file
+    /// reloads the defaults.
+    pub fn poll(&mut self) -> Option<Result<Config, String>> {
+        let now = Instant::now();
+        if self
+            .last_check
+            .is_some_and(|last| now.duration_since(last) < RELOAD_INTERVAL)
+        {
+            return None;
+        }
+        self.last_check = Some(now);
+
+        let modified = std::fs::metadata(&self.path)
+            .and_then(|meta| meta.modified())
+            .ok();
+        if modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+
+        Some(Config::read(&self.path.to_string_lossy()))
+    }
+}
+
+/// The table name of a `[table]` header line (ignoring indentation and any trailing comment), or
+/// `None` if `line` isn't a header. Array-of-tables headers get a leading `[` on the name.
+fn table_header(line: &str) -> Option<&str> {
+    let line = line.trim_start();
+    let inner = line.strip_prefix('[')?;
+    let close = inner.rfind(']')?;
+    let rest = inner[close + 1..].trim_start();
+    (rest.is_empty() || rest.starts_with('#')).then(|| inner[..close].trim())
+}
+
+/// The bare key a `key = value` line assigns, or `None` for blank lines and comments.
+fn assigned_key(line: &str) -> Option<&str> {
+    let line = line.trim_start();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    line.split('=').next().map(str::trim)
+}
+
+/// Whether a table name or key is `macros` itself or a dotted key inside it.
+fn is_macros_key(key: &str) -> bool {
+    key.strip_prefix("macros")
+        .is_some_and(|rest| rest.is_empty() || rest.trim_start().starts_with('.'))
+}
+
+/// `contents` with its `[macros]` table replaced by `table`, in the same place (or appended if it
+/// had none), keeping the blank lines and comments around it.
+fn replace_macros_table(contents: &str, table: &str) -> Result<String, String> {
+    let unsupported = |form: &str| {
+        Err(format!(
+            "can't rewrite macros defined {form}; move them into a [macros] table"
+        ))
+    };
+    let table = table.trim();
+    let mut lines: Vec<&str> = Vec::new();
+    let mut replaced = false;
+    let mut section: Option<&str> = None;
+    for line in contents.lines() {
+        if let Some(name) = table_header(line) {
+            if name != "macros" && is_macros_key(name.trim_matches(['[', ']'])) {
+                return unsupported(&format!("in a [{name}] table"));
+            }
+            if name == "macros" {
+                if replaced {
+                    return Err("more than one [macros] table".to_string());
+                }
+                replaced = true;
+                lines.push(table);
+                section = Some(name);
+                continue;
+            }
+            section = Some(name);
+        } else if section.is_none() && assigned_key(line).is_some_and(is_macros_key) {
+            return unsupported("as a dotted key or inline table");
+        } else if section == Some("macros") {
+            // Old entries are dropped, but the blank lines and comments before the next table stay
+            if assigned_key(line).is_some() {
+                continue;
+            }
+        }
+        lines.push(line);
+    }
+    let mut updated = lines.join("\n");
+    if !replaced {
+        let kept = updated.trim_end().len();
+        updated.truncate(kept);
+        if !updated.is_empty() {
+            updated.push_str("\n\n");
+        }
+        updated.push_str(table);
+    }
+    updated.push('\n');
+    Ok(updated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::replace_macros_table;
+
+    const TABLE: &str = "[macros]\nq = \"j j\"\n";
+
+    #[test]
+    fn replaces_the_macros_table_in_place() {
+        let contents = "a = 1\n\n[macros] # registers\nq = \"k\"\nw = \"l\"\n\n[next]\nb = 2\n";
+        assert_eq!(
+            replace_macros_table(contents, TABLE).unwrap(),
+            "a = 1\n\n[macros]\nq = \"j j\"\n\n[next]\nb = 2\n"
+        );
+    }
+
+    #[test]
+    fn appends_a_missing_macros_table() {
+        assert_eq!(
+            replace_macros_table("a = 1\n\n\n", TABLE).unwrap(),
+            "a = 1\n\n[macros]\nq = \"j j\"\n"
+        );
+        assert_eq!(replace_macros_table("", TABLE).unwrap(), TABLE);
+    }
+
+    #[test]
+    fn finds_indented_headers() {
+        let contents = "  [macros]\nq = \"k\"\n";
+        assert_eq!(replace_macros_table(contents, TABLE).unwrap(), TABLE);
+    }
+
+    #[test]
+    fn rejects_macros_it_cannot_rewrite() {
+        for contents in [
+            "macros.q = \"k\"\n",
+            "macros = { q = \"k\" }\n",
+            "[macros.q]\nsteps = \"k\"\n",
+            "[[macros]]\nq = \"k\"\n",
+            "[macros]\n[macros]\n",
+        ] {
+            assert!(replace_macros_table(contents, TABLE).is_err(), "{contents}");
+        }
+    }
+
+    #[test]
+    fn ignores_macros_keys_in_other_tables() {
+        let contents = "[other]\nmacros = 1\n";
+        assert_eq!(
+            replace_macros_table(contents, TABLE).unwrap(),
+            "[other]\nmacros = 1\n\n[macros]\nq = \"j j\"\n"
+        );
     }
 }
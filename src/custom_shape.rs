@@ -0,0 +1,266 @@
+//! User-defined point shapes declared in config, as polygon vertices or an SVG path.
+
+use eframe::egui;
+use facet::Facet;
+
+/// Line segments used to flatten each curve in an SVG path.
+const CURVE_SEGMENTS: u16 = 8;
+
+/// A custom glyph in unit coordinates: the point's centre is the origin and
+/// its radius is 1, so the outline should lie within `-1.0..=1.0` on each axis.
+///
+/// ```toml
+/// [custom_shapes.arrow]
+/// vertices = [[0.0, -1.0], [1.0, 0.0], [0.4, 0.0], [0.4, 1.0], [-0.4, 1.0], [-0.4, 0.0], [-1.0, 0.0]]
+///
+/// [custom_shapes.chevron]
+/// path = "M -1 -0.5 L 0 0.5 L 1 -0.5 L 1 0 L 0 1 L -1 0 Z"
+/// ```
+#[derive(Facet, Clone)]
+pub struct CustomShape {
+    /// Polygon vertices as `[x, y]` pairs.
+    #[facet(default)]
+    pub vertices: Vec<Vec<f32>>,
+    /// A single closed SVG path, used instead of `vertices` when set.
+    /// Supports `M`, `L`, `H`, `V`, `C`, `Q` and `Z` (absolute and relative); curves are flattened.
+    #[facet(default)]
+    pub path: Option<String>,
+}
+
+impl CustomShape {
+    /// The outline polygon in unit coordinates.
+    ///
+    /// # Errors
+    ///
+    /// Returns a message if a vertex isn't an `[x, y]` pair, the path can't be parsed,
+    /// or there are fewer than three vertices.
+    pub fn outline(&self) -> Result<Vec<egui::Vec2>, String> {
+        let outline = match &self.path {
+            Some(path) => parse_path(path)?,
+            None => self
+                .vertices
+                .iter()
+                .map(|vertex| match vertex.as_slice() {
+                    [x, y] => Ok(egui::vec2(*x, *y)),
+                    _ => Err(format!("vertex {vertex:?} is not an [x, y] pair")),
+                })
+                .collect::<Result<_, _>>()?,
+        };
+        if outline.len() < 3 {
+            return Err("a custom shape needs at least three vertices".to_string());
+        }
+        Ok(outline)
+    }
+}
+
+/// Flatten an SVG path into the vertices of a single polygon.
+fn parse_path(path: &str) -> Result<Vec<egui::Vec2>, String> {
+    let tokens = tokenize(path)?;
+    let mut outline: Vec<egui::Vec2> = Vec::new();
+    let mut current = egui::Vec2::ZERO;
+    let mut start = egui::Vec2::ZERO;
+    let mut command = None;
+    let mut i = 0;
+
+    let number = |i: &mut usize| -> Result<f32, String> {
+        match tokens.get(*i) {
+            Some(Token::Number(n)) => {
+                *i += 1;
+                Ok(*n)
+            }
+            _ => Err(format!("expected a number in path `{path}`")),
+        }
+    };
+
+    while i < tokens.len() {
+        if let Token::Command(c) = tokens[i] {
+            command = Some(c);
+            i += 1;
+            if c.eq_ignore_ascii_case(&'z') {
+                current = start;
+                continue;
+            }
+        }
+        let c = command.ok_or_else(|| format!("path `{path}` must start with a command"))?;
+        let relative = c.is_ascii_lowercase();
+        let base = if relative { current } else { egui::Vec2::ZERO };
+
+        match c.to_ascii_uppercase() {
+            'M' | 'L' => {
+                let p = base + egui::vec2(number(&mut i)?, number(&mut i)?);
+                if c.eq_ignore_ascii_case(&'m') {
+                    if !outline.is_empty() {
+                        return Err(format!("path `{path}` must be a single outline"));
+                    }
+                    start = p;
+                    // Further coordinate pairs after a move are line-tos
+                    command = Some(if relative { 'l' } else { 'L' });
+                }
+                outline.push(p);
+                current = p;
+            }
+            'H' => {
+                let x = number(&mut i)? + if relative { current.x } else { 0.0 };
+                current = egui::vec2(x, current.y);
+                outline.push(current);
+            }
+            'V' => {
+                let y = number(&mut i)? + if relative { current.y } else { 0.0 };
+                current = egui::vec2(current.x, y);
+                outline.push(current);
+            }
+            'Q' => {
+                let control = base + egui::vec2(number(&mut i)?, number(&mut i)?);
+                let end = base + egui::vec2(number(&mut i)?, number(&mut i)?);
+                for step in 1..=CURVE_SEGMENTS {
+                    let t = f32::from(step) / f32::from(CURVE_SEGMENTS);
+                    let u = 1.0 - t;
+                    outline.push(current * u * u + control * 2.0 * u * t + end * t * t);
+                }
+                current = end;
+            }
+            'C' => {
+                let c1 = base + egui::vec2(number(&mut i)?, number(&mut i)?);
+                let c2 = base + egui::vec2(number(&mut i)?, number(&mut i)?);
+                let end = base + egui::vec2(number(&mut i)?, number(&mut i)?);
+                for step in 1..=CURVE_SEGMENTS {
+                    let t = f32::from(step) / f32::from(CURVE_SEGMENTS);
+                    let u = 1.0 - t;
+                    outline.push(
+                        current * u * u * u
+                            + c1 * 3.0 * u * u * t
+                            + c2 * 3.0 * u * t * t
+                            + end * t * t * t,
+                    );
+                }
+                current = end;
+            }
+            other => return Err(format!("unsupported path command `{other}`")),
+        }
+    }
+
+    // Closing back onto the start doesn't need a duplicate vertex
+    if outline.len() > 1 && outline.first() == outline.last() {
+        outline.pop();
+    }
+    Ok(outline)
+}
+
+enum Token {
+    Command(char),
+    Number(f32),
+}
+
+fn tokenize(path: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let chars: Vec<char> = path.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() || c == ',' {
+            i += 1;
+        } else if c.is_ascii_alphabetic() && !matches!(c, 'e' | 'E') {
+            tokens.push(Token::Command(c));
+            i += 1;
+        } else {
+            let begin = i;
+            i += 1;
+            while i < chars.len() {
+                let next = chars[i];
+                let exponent_sign = matches!(next, '-' | '+') && matches!(chars[i - 1], 'e' | 'E');
+                if next.is_ascii_digit() || matches!(next, '.' | 'e' | 'E') || exponent_sign {
+                    i += 1;
+                } else {
+                    break;
+                }
+            }
+            let text: String = chars[begin..i].iter().collect();
+            let n = text
+                .parse()
+                .map_err(|_| format!("can't parse `{text}` in path `{path}`"))?;
+            tokens.push(Token::Number(n));
+        }
+    }
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{tokenize, CustomShape, Token, CURVE_SEGMENTS};
+    use eframe::egui;
+
+    fn path(path: &str) -> Result<Vec<egui::Vec2>, String> {
+        CustomShape {
+            vertices: Vec::new(),
+            path: Some(path.to_string()),
+        }
+        .outline()
+    }
+
+    #[test]
+    fn tokenizes_packed_numbers_and_exponents() {
+        let tokens = tokenize("M1-2.5,3e-1 .5l-1E+1 0z").unwrap();
+        let numbers: Vec<f32> = tokens
+            .iter()
+            .filter_map(|token| match token {
+                Token::Number(n) => Some(*n),
+                Token::Command(_) => None,
+            })
+            .collect();
+        assert_eq!(numbers, [1.0, -2.5, 0.3, 0.5, -10.0, 0.0]);
+        let commands: String = tokens
+            .iter()
+            .filter_map(|token| match token {
+                Token::Command(c) => Some(*c),
+                Token::Number(_) => None,
+            })
+            .collect();
+        assert_eq!(commands, "Mlz");
+        assert!(tokenize("M 1 -").is_err());
+    }
+
+    #[test]
+    fn follows_relative_and_axis_commands() {
+        let outline = path("m -1 -1 h 2 v 2 H -1 Z").unwrap();
+        assert_eq!(
+            outline,
+            [
+                egui::vec2(-1.0, -1.0),
+                egui::vec2(1.0, -1.0),
+                egui::vec2(1.0, 1.0),
+                egui::vec2(-1.0, 1.0),
+            ]
+        );
+        // Extra pairs after a move are lines
+        assert_eq!(path("M 0 0 1 0 1 1").unwrap().len(), 3);
+    }
+
+    #[test]
+    fn flattens_curves_onto_their_end_points() {
+        let outline = path("M -1 0 Q 0 -2 1 0 C 1 1 -1 1 -1 0 Z").unwrap();
+        let segments = usize::from(CURVE_SEGMENTS);
+        // The closing point of the cubic coincides with the start, so it is dropped
+        assert_eq!(outline.len(), 1 + 2 * segments - 1);
+        assert_eq!(outline[segments], egui::vec2(1.0, 0.0));
+        // A quadratic's midpoint lies halfway between its control point and the chord
+        assert!((outline[segments / 2] - egui::vec2(0.0, -1.0)).length() < 1e-5);
+    }
+
+    #[test]
+    fn rejects_invalid_shapes() {
+        for bad in [
+            "0 0 L 1 1 1 0",
+            "M 0 0 L 1",
+            "M 0 0 A 1 1 0 0 1 1 1",
+            "M 0 0 L 1 1 Z M 2 2 L 3 3",
+            "M 0 0 L 1 1",
+        ] {
+            assert!(path(bad).is_err(), "{bad}");
+        }
+        let shape = CustomShape {
+            vertices: vec![vec![0.0, 0.0], vec![1.0], vec![1.0, 1.0]],
+            path: None,
+        };
+        assert!(shape.outline().is_err());
+    }
+}
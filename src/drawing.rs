@@ -1,136 +1,584 @@
 //! Canvas rendering logic.
 
 use crate::config::Config;
-use crate::persistence::PointShape;
-use crate::state::AppState;
+use crate::geometry::{self, Primitive};
+use crate::grid::{self, GridLine};
+use crate::measure;
+use crate::persistence::Point;
+use crate::snap::{GuideKind, GAP_TICK};
+use crate::state::{AppState, InteractionMode};
 use eframe::egui;
 
+/// Thickness of the rulers along the top and left of the canvas, in screen points.
+pub const RULER_SIZE: f32 = 20.0;
+
+/// Ruler labels are at least this many screen points apart.
+const RULER_LABEL_SPACING: f32 = 60.0;
+
 pub fn draw_canvas(ui: &mut egui::Ui, state: &AppState, config: &Config) -> egui::Response {
     let (response, painter) =
         ui.allocate_painter(ui.available_size(), egui::Sense::click_and_drag());
 
-    let bg = Config::parse_colour(&config.bg_color);
+    let bg = Config::colour(&config.bg_color);
     painter.rect_filled(response.rect, 0.0, bg);
 
+    let canvas = painter.with_clip_rect(response.rect);
+
+    if config.show_background {
+        draw_background(&canvas, state);
+    }
     if config.grid_enabled {
-        draw_grid(&painter, &response.rect, config);
+        draw_grid(&canvas, state.zoom, config);
+    }
+    if config.show_axes {
+        draw_axes(&canvas, config);
+    }
+
+    draw_paths(&canvas, state, config);
+    draw_edges(&canvas, state, config);
+    draw_points(&canvas, state, config);
+    if config.show_duplicates {
+        draw_duplicates(&canvas, state, config);
+    }
+    draw_labels(&canvas, state, config);
+    draw_guides(&canvas, state, config);
+    draw_ruler_guides(&canvas, state, config);
+    if state.interaction_mode == InteractionMode::Measure {
+        draw_measure(&canvas, state, config);
     }
 
-    draw_points(&painter, state, config);
+    if config.show_rulers {
+        draw_rulers(&painter, response.rect, state, config);
+    }
 
     response
 }
 
-fn draw_grid(painter: &egui::Painter, rect: &egui::Rect, config: &Config) {
-    let grid_color = Config::parse_colour(&config.grid_color);
-    let spacing = config.grid_spacing;
+/// The reference image, faded to its opacity.
+fn draw_background(painter: &egui::Painter, state: &AppState) {
+    let (Some(background), Some(texture), Some(rect)) = (
+        &state.background,
+        &state.background_texture,
+        state.background_rect(),
+    ) else {
+        return;
+    };
+    let uv = egui::Rect::from_min_max(egui::Pos2::ZERO, egui::pos2(1.0, 1.0));
+    let tint = egui::Color32::WHITE.gamma_multiply(background.opacity);
+    painter.image(texture.id(), rect, uv, tint);
+}
+
+/// Grid lines, thinned out as `zoom` falls so they never merge into a solid fill.
+fn draw_grid(painter: &egui::Painter, zoom: f32, config: &Config) {
+    let minor = egui::Stroke::new(1.0, Config::colour(&config.grid_color));
+    let major = egui::Stroke::new(
+        config.grid_major_width,
+        Config::colour(&config.grid_major_color),
+    );
+    let spacing = grid::visible_spacing(config, zoom);
+    let lines = grid::lines(config, painter.clip_rect(), spacing);
+    // Majors on top, so minor lines crossing them don't break them up
+    for draw_major in [false, true] {
+        for line in &lines {
+            match *line {
+                GridLine::Segment { points, major: m } if m == draw_major => {
+                    painter.line_segment(points, if m { major } else { minor });
+                }
+                GridLine::Circle {
+                    center,
+                    radius,
+                    major: m,
+                } if m == draw_major => {
+                    painter.circle_stroke(center, radius, if m { major } else { minor });
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// The x and y axes, across the whole visible canvas.
+fn draw_axes(painter: &egui::Painter, config: &Config) {
+    let stroke = egui::Stroke::new(config.grid_major_width, Config::colour(&config.axis_color));
+    let rect = painter.clip_rect();
+    painter.hline(rect.x_range(), 0.0, stroke);
+    painter.vline(0.0, rect.y_range(), stroke);
+}
+
+/// Ruler guides, across the whole visible canvas.
+fn draw_ruler_guides(painter: &egui::Painter, state: &AppState, config: &Config) {
+    let stroke = egui::Stroke::new(1.0, Config::colour(&config.ruler_guide_color));
+    let rect = painter.clip_rect();
+    for guide in &state.ruler_guides {
+        if guide.vertical {
+            painter.vline(guide.position, rect.y_range(), stroke);
+        } else {
+            painter.hline(rect.x_range(), guide.position, stroke);
+        }
+    }
+}
+
+/// The measured segments and the one on to the cursor, each labelled with its length and angle,
+/// and the angle at each corner.
+fn draw_measure(painter: &egui::Painter, state: &AppState, config: &Config) {
+    let color = Config::colour(&config.measure_color);
+    let stroke = egui::Stroke::new(1.5, color);
+    let font = egui::FontId::proportional(config.label_font_size);
+    let mut anchors = state.measure.clone();
+    anchors.extend(state.measure.last().and(state.cursor));
 
-    let mut x = (rect.min.x / spacing).ceil() * spacing;
-    while x < rect.max.x {
-        painter.line_segment(
-            [egui::pos2(x, rect.min.y), egui::pos2(x, rect.max.y)],
-            egui::Stroke::new(1.0, grid_color),
+    for &anchor in &state.measure {
+        painter.circle_filled(anchor, 3.0, color);
+    }
+    for pair in anchors.windows(2) {
+        let [from, to] = [pair[0], pair[1]];
+        painter.line_segment([from, to], stroke);
+        painter.text(
+            from.lerp(to, 0.5),
+            egui::Align2::LEFT_BOTTOM,
+            measure::describe(from, to),
+            font.clone(),
+            color,
+        );
+    }
+    for corner in anchors.windows(3) {
+        painter.text(
+            corner[1],
+            egui::Align2::RIGHT_TOP,
+            format!(
+                "{:.1}°",
+                measure::corner_angle(corner[0], corner[1], corner[2])
+            ),
+            font.clone(),
+            color,
         );
-        x += spacing;
     }
+}
+
+/// A round step in canvas units between ruler labels at `zoom`, and the ticks dividing it.
+fn ruler_step(zoom: f32) -> (f32, u8) {
+    let min = RULER_LABEL_SPACING / zoom;
+    let magnitude = 10.0_f32.powf(min.log10().floor());
+    [(1.0, 10), (2.0, 4), (5.0, 5)]
+        .into_iter()
+        .map(|(mantissa, ticks)| (mantissa * magnitude, ticks))
+        .find(|&(step, _)| step >= min)
+        .unwrap_or((10.0 * magnitude, 10))
+}
+
+/// Rulers along the top and left of the canvas in canvas units, marking the cursor.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn draw_rulers(painter: &egui::Painter, rect: egui::Rect, state: &AppState, config: &Config) {
+    let text_color = egui::Color32::from_gray(60);
+    let stroke = egui::Stroke::new(1.0, egui::Color32::from_gray(120));
+    let font = egui::FontId::proportional(9.0);
+    let (step, ticks) = ruler_step(state.zoom);
+    let tick = step / f32::from(ticks);
+    let decimals = (-step.log10()).ceil().max(0.0) as usize;
 
-    let mut y = (rect.min.y / spacing).ceil() * spacing;
-    while y < rect.max.y {
-        painter.line_segment(
-            [egui::pos2(rect.min.x, y), egui::pos2(rect.max.x, y)],
-            egui::Stroke::new(1.0, grid_color),
+    let top = egui::Rect::from_min_max(rect.min, egui::pos2(rect.max.x, rect.min.y + RULER_SIZE));
+    let left = egui::Rect::from_min_max(rect.min, egui::pos2(rect.min.x + RULER_SIZE, rect.max.y));
+    for ruler in [top, left] {
+        painter.rect_filled(ruler, 0.0, egui::Color32::from_gray(235));
+    }
+    let start = rect.min + egui::Vec2::splat(RULER_SIZE);
+
+    for k in grid::steps(rect.min.x, rect.max.x, tick) {
+        let x = k * tick;
+        if x < start.x || x > rect.max.x {
+            continue;
+        }
+        let major = k.rem_euclid(f32::from(ticks)) == 0.0;
+        let length = if major { RULER_SIZE } else { RULER_SIZE / 4.0 };
+        painter.vline(x, egui::Rangef::new(top.max.y - length, top.max.y), stroke);
+        if major {
+            let text = format!("{:.decimals$}", k * tick);
+            painter.text(
+                egui::pos2(x + 2.0, top.min.y + 1.0),
+                egui::Align2::LEFT_TOP,
+                text,
+                font.clone(),
+                text_color,
+            );
+        }
+    }
+    for k in grid::steps(rect.min.y, rect.max.y, tick) {
+        let y = k * tick;
+        if y < start.y || y > rect.max.y {
+            continue;
+        }
+        let major = k.rem_euclid(f32::from(ticks)) == 0.0;
+        let length = if major { RULER_SIZE } else { RULER_SIZE / 4.0 };
+        painter.hline(
+            egui::Rangef::new(left.max.x - length, left.max.x),
+            y,
+            stroke,
         );
-        y += spacing;
+        if major {
+            // Reading upwards, so long numbers fit the ruler's width
+            let galley = painter.layout_no_wrap(
+                format!("{:.decimals$}", k * tick),
+                font.clone(),
+                text_color,
+            );
+            painter.add(
+                egui::epaint::TextShape::new(
+                    egui::pos2(left.min.x + 1.0, y - 2.0),
+                    galley,
+                    text_color,
+                )
+                .with_angle(-std::f32::consts::FRAC_PI_2),
+            );
+        }
+    }
+
+    if let Some(cursor) = state.cursor {
+        let marker = egui::Stroke::new(1.0, Config::colour(&config.ruler_guide_color));
+        if cursor.x >= start.x {
+            painter.vline(cursor.x, top.y_range(), marker);
+        }
+        if cursor.y >= start.y {
+            painter.hline(left.x_range(), cursor.y, marker);
+        }
     }
+    painter.rect_filled(
+        egui::Rect::from_min_max(rect.min, start),
+        0.0,
+        egui::Color32::from_gray(235),
+    );
 }
 
-#[allow(clippy::cast_precision_loss)]
-fn draw_points(painter: &egui::Painter, state: &AppState, config: &Config) {
-    let point_color = Config::parse_colour(&config.point_color);
-    let selected_color = Config::parse_colour(&config.selected_color);
-    let selected_indices = state.selected_indices();
+/// Split a simple polygon into triangles by ear clipping, as vertex index triples.
+///
+/// Self-intersecting outlines can leave vertices over, which are fanned from the first.
+#[must_use]
+pub fn triangulate(outline: &[egui::Pos2]) -> Vec<[usize; 3]> {
+    let cross = |origin: egui::Pos2, u: egui::Pos2, v: egui::Pos2| {
+        (u - origin).x * (v - origin).y - (u - origin).y * (v - origin).x
+    };
+    let len = outline.len();
+    let signed_area: f32 = (0..len)
+        .map(|i| {
+            let (from, to) = (outline[i], outline[(i + 1) % len]);
+            from.x * to.y - to.x * from.y
+        })
+        .sum();
+    let mut remaining: Vec<usize> = (0..len).collect();
+    if signed_area < 0.0 {
+        remaining.reverse();
+    }
+
+    let mut triangles = Vec::new();
+    while remaining.len() > 3 {
+        let count = remaining.len();
+        let corners = |i: usize| {
+            [
+                remaining[(i + count - 1) % count],
+                remaining[i],
+                remaining[(i + 1) % count],
+            ]
+        };
+        let is_ear = |i: usize| {
+            let [prev, here, next] = corners(i).map(|idx| outline[idx]);
+            cross(prev, here, next) > 0.0
+                && remaining.iter().all(|&other| {
+                    let pos = outline[other];
+                    [prev, here, next].contains(&pos)
+                        || cross(prev, here, pos) < 0.0
+                        || cross(here, next, pos) < 0.0
+                        || cross(next, prev, pos) < 0.0
+                })
+        };
+        let Some(ear) = (0..count).find(|&i| is_ear(i)) else {
+            break;
+        };
+        triangles.push(corners(ear));
+        remaining.remove(ear);
+    }
+    for i in 1..remaining.len().saturating_sub(1) {
+        triangles.push([remaining[0], remaining[i], remaining[i + 1]]);
+    }
+    triangles
+}
 
-    for (i, pt) in state.points.iter().enumerate() {
-        let pos = egui::pos2(pt.x, pt.y);
-        let color = if selected_indices.contains(&i) || state.dragging == Some(i) {
+/// Fill any simple polygon, since `convex_polygon` can't fill concave outlines.
+fn concave_filled(painter: &egui::Painter, outline: &[egui::Pos2], color: egui::Color32) {
+    let mut mesh = egui::Mesh::default();
+    for pos in outline {
+        mesh.colored_vertex(*pos, color);
+    }
+    for [a, b, c] in triangulate(outline) {
+        let index = |i: usize| u32::try_from(i).unwrap_or(u32::MAX);
+        mesh.add_triangle(index(a), index(b), index(c));
+    }
+    painter.add(egui::Shape::mesh(mesh));
+}
+
+/// Paths as lines, or filled regions when closed; the current path is outlined in `selected_color`.
+fn draw_paths(painter: &egui::Painter, state: &AppState, config: &Config) {
+    let color = Config::colour(&config.path_color);
+    let fill = color.gamma_multiply(config.path_fill_opacity.clamp(0.0, 1.0));
+    let selected_color = Config::colour(&config.selected_color);
+    // Paths skip points on hidden layers
+    let visible: Vec<Point> = state
+        .points
+        .iter()
+        .filter(|pt| state.is_visible(pt))
+        .cloned()
+        .collect();
+    for (idx, path) in state.paths.iter().enumerate() {
+        let vertices = geometry::path_vertices(path, &visible);
+        let stroke_color = if state.current_path == Some(idx) {
             selected_color
         } else {
-            point_color
+            color
         };
+        let stroke = egui::Stroke::new(config.path_width, stroke_color);
+        if path.closed {
+            concave_filled(painter, &vertices, fill);
+            painter.add(egui::Shape::closed_line(vertices, stroke));
+        } else {
+            painter.add(egui::Shape::line(vertices, stroke));
+        }
+    }
+}
 
-        match pt.shape {
-            PointShape::Circle => {
-                painter.circle_filled(pos, config.point_radius, color);
-            }
-            PointShape::Square => {
-                let half = config.point_radius;
-                let corners = [(-half, -half), (half, -half), (half, half), (-half, half)];
-
-                let rotated_corners: Vec<egui::Pos2> = corners
-                    .iter()
-                    .map(|(x, y)| {
-                        let rotated_x = x * pt.rotation.cos() - y * pt.rotation.sin();
-                        let rotated_y = x * pt.rotation.sin() + y * pt.rotation.cos();
-                        egui::pos2(pos.x + rotated_x, pos.y + rotated_y)
-                    })
-                    .collect();
-
-                painter.add(egui::Shape::convex_polygon(
-                    rotated_corners,
-                    color,
-                    egui::Stroke::NONE,
-                ));
-            }
-            PointShape::Diamond => {
-                let r = config.point_radius;
-                let corners = [(0.0, -r), (r, 0.0), (0.0, r), (-r, 0.0)];
-
-                let rotated_corners: Vec<egui::Pos2> = corners
-                    .iter()
-                    .map(|(x, y)| {
-                        let rotated_x = x * pt.rotation.cos() - y * pt.rotation.sin();
-                        let rotated_y = x * pt.rotation.sin() + y * pt.rotation.cos();
-                        egui::pos2(pos.x + rotated_x, pos.y + rotated_y)
-                    })
-                    .collect();
-
-                painter.add(egui::Shape::convex_polygon(
-                    rotated_corners,
-                    color,
-                    egui::Stroke::NONE,
-                ));
+/// Labels (and ids, when shown) beside their points, on top of every shape.
+fn draw_labels(painter: &egui::Painter, state: &AppState, config: &Config) {
+    let color = Config::colour(&config.label_color);
+    let font = egui::FontId::proportional(config.label_font_size);
+    for idx in state.draw_order() {
+        let pt = &state.points[idx];
+        if let Some(text) = geometry::label_text(pt, config.show_ids) {
+            let (pos, anchor) = geometry::label_position(pt, config);
+            let color = color.gamma_multiply(state.opacity(pt));
+            painter.text(pos, anchor, text, font.clone(), color);
+        }
+    }
+}
+
+/// Lines between connected points, drawn before the points so they sit underneath.
+///
+/// An edge is hidden with either end, and as faint as the fainter end's layer.
+fn draw_edges(painter: &egui::Painter, state: &AppState, config: &Config) {
+    let edge_color = Config::colour(&config.edge_color);
+    for (edge, from, to) in state.edge_endpoints() {
+        if !state.is_visible(from) || !state.is_visible(to) {
+            continue;
+        }
+        let Some([start, end]) = geometry::edge_segment(from, to, config) else {
+            continue;
+        };
+        let color = edge_color.gamma_multiply(state.opacity(from).min(state.opacity(to)));
+        let stroke = egui::Stroke::new(config.edge_width, color);
+        if edge.directed {
+            let head = geometry::arrowhead(end, end - start, config.edge_width);
+            // Stop the line at the arrowhead's base so it doesn't blunt the tip
+            painter.line_segment([start, head[1].lerp(head[2], 0.5)], stroke);
+            painter.add(egui::Shape::convex_polygon(
+                head.to_vec(),
+                color,
+                egui::Stroke::NONE,
+            ));
+        } else {
+            painter.line_segment([start, end], stroke);
+        }
+    }
+}
+
+/// Paint a shape with the given fill (transparent for none) and outline.
+fn draw_primitive(
+    painter: &egui::Painter,
+    primitive: Primitive,
+    fill: egui::Color32,
+    stroke: egui::Stroke,
+) {
+    match primitive {
+        Primitive::Circle { center, radius } => {
+            painter.circle(center, radius, fill, stroke);
+        }
+        Primitive::Ring {
+            center,
+            radius,
+            width,
+        } => {
+            painter.circle_stroke(center, radius, egui::Stroke::new(width, fill));
+            // Outlining both edges of a hairline band would hide it
+            if width > 2.0 * stroke.width {
+                painter.circle_stroke(center, radius - width / 2.0, stroke);
+                painter.circle_stroke(center, radius + width / 2.0, stroke);
             }
-            PointShape::Semicircle => {
-                let r = config.point_radius;
-                let segments = 16;
+        }
+        Primitive::Polygon {
+            vertices,
+            convex: true,
+        } => {
+            painter.add(egui::Shape::convex_polygon(vertices, fill, stroke));
+        }
+        Primitive::Polygon {
+            vertices,
+            convex: false,
+        } => {
+            concave_filled(painter, &vertices, fill);
+            painter.add(egui::Shape::closed_line(vertices, stroke));
+        }
+    }
+}
 
-                let mut points = Vec::new();
+/// Stroke around a shape's outer edge, drawn underneath it to mark a selection.
+fn draw_halo(painter: &egui::Painter, primitive: &Primitive, halo: egui::Stroke) {
+    match primitive {
+        Primitive::Circle { center, radius } => {
+            painter.circle_stroke(*center, *radius, halo);
+        }
+        Primitive::Ring {
+            center,
+            radius,
+            width,
+        } => {
+            painter.circle_stroke(*center, radius + width / 2.0, halo);
+        }
+        Primitive::Polygon { vertices, .. } => {
+            painter.add(egui::Shape::closed_line(vertices.clone(), halo));
+        }
+    }
+}
 
-                // Create semi-circle from 0 to π
-                for i in 0..=segments {
-                    let angle = std::f32::consts::PI * i as f32 / segments as f32;
-                    let x = r * angle.cos();
-                    let y = -r * angle.sin();
+/// Alignment lines, and equal gaps marked with end ticks, for the drag in progress.
+fn draw_guides(painter: &egui::Painter, state: &AppState, config: &Config) {
+    let stroke = egui::Stroke::new(1.0, Config::colour(&config.guide_color));
+    for guide in &state.guides {
+        painter.line_segment([guide.from, guide.to], stroke);
+        if guide.kind == GuideKind::Gap {
+            let tick = (guide.to - guide.from).normalized().rot90() * GAP_TICK;
+            for end in [guide.from, guide.to] {
+                painter.line_segment([end - tick, end + tick], stroke);
+            }
+        }
+    }
+}
 
-                    // Apply rotation
-                    let rotated_x = x * pt.rotation.cos() - y * pt.rotation.sin();
-                    let rotated_y = x * pt.rotation.sin() + y * pt.rotation.cos();
+/// An outline round each set of duplicate points, with how many it holds.
+fn draw_duplicates(painter: &egui::Painter, state: &AppState, config: &Config) {
+    let color = Config::colour(&config.duplicate_color);
+    let stroke = egui::Stroke::new(2.0, color);
+    for set in state.duplicate_sets(config.duplicate_tolerance) {
+        if !set.iter().any(|&idx| state.is_point_visible(idx)) {
+            continue;
+        }
+        let rect = set
+            .iter()
+            .map(|&idx| geometry::bounds(&state.points[idx], config))
+            .fold(egui::Rect::NOTHING, egui::Rect::union)
+            .expand(config.selected_halo_width + 2.0);
+        painter.rect_stroke(rect, 2.0, stroke);
+        painter.text(
+            rect.right_top(),
+            egui::Align2::LEFT_BOTTOM,
+            format!("×{}", set.len()),
+            egui::FontId::proportional(config.label_font_size),
+            color,
+        );
+    }
+}
 
-                    points.push(egui::pos2(pos.x + rotated_x, pos.y + rotated_y));
-                }
+fn draw_points(painter: &egui::Painter, state: &AppState, config: &Config) {
+    let point_color = Config::colour(&config.point_color);
+    let selected_color = Config::colour(&config.selected_color);
+    let selected_indices = state.selected_indices();
 
-                painter.add(egui::Shape::convex_polygon(
-                    points,
-                    color,
-                    egui::Stroke::NONE,
-                ));
-            }
+    for i in state.draw_order() {
+        let pt = &state.points[i];
+        let opacity = state.opacity(pt);
+        let style = config.style_for(pt);
+        let fill = if style.fill {
+            point_color.gamma_multiply(opacity)
+        } else {
+            egui::Color32::TRANSPARENT
+        };
+        let stroke = egui::Stroke::new(
+            style.stroke_width,
+            Config::colour(&style.stroke_color).gamma_multiply(opacity),
+        );
+        let primitive = geometry::primitive(pt, config);
+
+        if selected_indices.contains(&i) || state.dragging == Some(i) {
+            // Centred on the edge, so it extends the halo width beyond the outline
+            let halo = egui::Stroke::new(
+                style.stroke_width + 2.0 * config.selected_halo_width,
+                selected_color.gamma_multiply(opacity),
+            );
+            draw_halo(painter, &primitive, halo);
         }
+        draw_primitive(painter, primitive, fill, stroke);
     }
 
     if let (Some(start), Some(end)) = (state.box_select_start, state.box_select_end) {
-        let box_color = Config::parse_colour(&config.selection_box_color);
+        let box_color = Config::colour(&config.selection_box_color);
         let rect = egui::Rect::from_two_pos(start, end);
         painter.rect_stroke(rect, 0.0, egui::Stroke::new(2.0, box_color));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::triangulate;
+    use eframe::egui::{pos2, Pos2};
+
+    fn area(a: Pos2, b: Pos2, c: Pos2) -> f32 {
+        ((b - a).x * (c - a).y - (b - a).y * (c - a).x) / 2.0
+    }
+
+    /// Check the triangles exactly cover the outline, each wound the same way.
+    fn assert_covers(outline: &[Pos2], expected_area: f32) {
+        let triangles = triangulate(outline);
+        assert_eq!(triangles.len(), outline.len() - 2);
+        let areas: Vec<f32> = triangles
+            .iter()
+            .map(|&[a, b, c]| area(outline[a], outline[b], outline[c]))
+            .collect();
+        let winding = areas[0].signum();
+        assert!(areas.iter().all(|area| area * winding > 0.0));
+        let total: f32 = areas.iter().map(|area| area.abs()).sum();
+        assert!((total - expected_area).abs() < 1e-4, "{total}");
+    }
+
+    #[test]
+    fn triangulates_concave_outlines() {
+        // An L shape, and an arrow with a reflex corner either side of its shaft
+        let l_shape = [
+            pos2(0.0, 0.0),
+            pos2(2.0, 0.0),
+            pos2(2.0, 1.0),
+            pos2(1.0, 1.0),
+            pos2(1.0, 2.0),
+            pos2(0.0, 2.0),
+        ];
+        assert_covers(&l_shape, 3.0);
+        let arrow = [
+            pos2(0.0, -1.0),
+            pos2(1.0, 0.0),
+            pos2(0.4, 0.0),
+            pos2(0.4, 1.0),
+            pos2(-0.4, 1.0),
+            pos2(-0.4, 0.0),
+            pos2(-1.0, 0.0),
+        ];
+        assert_covers(&arrow, 1.0 + 0.8);
+    }
+
+    #[test]
+    fn handles_either_winding() {
+        let mut l_shape = vec![
+            pos2(0.0, 0.0),
+            pos2(2.0, 0.0),
+            pos2(2.0, 1.0),
+            pos2(1.0, 1.0),
+            pos2(1.0, 2.0),
+            pos2(0.0, 2.0),
+        ];
+        l_shape.reverse();
+        assert_covers(&l_shape, 3.0);
+        assert_eq!(triangulate(&l_shape[..3]).len(), 1);
+    }
+}
@@ -0,0 +1,313 @@
+//! SVG export of the canvas.
+
+use crate::config::Config;
+use crate::geometry::{self, Primitive};
+use crate::persistence::{self, Document, Layer, Path, Point};
+use eframe::egui;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+
+const SVG_FILE: &str = "points.svg";
+
+/// Margin around the points' bounding box, in canvas units.
+const MARGIN: f32 = 10.0;
+
+/// Typical character width as a fraction of the font size, for sizing labels.
+const LABEL_CHAR_WIDTH: f32 = 0.6;
+
+/// Render the points as a standalone SVG document, cropped to their bounding box.
+///
+/// Hidden points and layers are left out; each shown layer becomes a `<g>` carrying its opacity.
+#[must_use]
+pub fn to_svg(document: &Document, config: &Config) -> String {
+    let visible = |pt: &&Point| {
+        !pt.hidden && persistence::layer_of(&document.layers, pt).is_none_or(|l| l.visible)
+    };
+    let document = &Document {
+        points: document.points.iter().filter(visible).cloned().collect(),
+        edges: document.edges.clone(),
+        paths: document.paths.clone(),
+        layers: document.layers.clone(),
+        groups: document.groups.clone(),
+        guides: document.guides.clone(),
+        background: document.background.clone(),
+    };
+    let points = &document.points;
+    let bounds = points.iter().fold(egui::Rect::NOTHING, |rect, pt| {
+        let rect = rect.union(geometry::bounds(pt, config));
+        match &pt.label {
+            Some(label) => rect.union(label_bounds(pt, label, config)),
+            None => rect,
+        }
+    });
+    let bounds = if points.is_empty() {
+        egui::Rect::from_min_size(egui::Pos2::ZERO, egui::Vec2::ZERO)
+    } else {
+        bounds.expand(MARGIN)
+    };
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {} {}" width="{}" height="{}">"#,
+        bounds.min.x,
+        bounds.min.y,
+        bounds.width(),
+        bounds.height(),
+        bounds.width(),
+        bounds.height(),
+    );
+    let _ = writeln!(
+        svg,
+        r#"  <rect x="{}" y="{}" width="{}" height="{}" fill="{}"/>"#,
+        bounds.min.x,
+        bounds.min.y,
+        bounds.width(),
+        bounds.height(),
+        config.bg_color,
+    );
+    for path in &document.paths {
+        let _ = writeln!(svg, "  {}", path_element(path, &document.points, config));
+    }
+    for element in edge_elements(document, config) {
+        let _ = writeln!(svg, "  {element}");
+    }
+    let groups = layer_groups(document);
+    for (layer, points) in &groups {
+        let elements = points.iter().flat_map(|pt| shape_elements(pt, config));
+        write_group(&mut svg, *layer, "", elements);
+    }
+    // Labels go over every layer's shapes, as on the canvas
+    for (layer, points) in &groups {
+        let elements = points.iter().filter_map(|pt| label_element(pt, config));
+        write_group(&mut svg, *layer, "-labels", elements);
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// The points on each layer, bottom first, then any on layers the document doesn't list.
+fn layer_groups(document: &Document) -> Vec<(Option<&Layer>, Vec<&Point>)> {
+    let mut groups: Vec<(Option<&Layer>, Vec<&Point>)> = document
+        .layers
+        .iter()
+        .map(|layer| {
+            let points = document.points.iter().filter(|pt| pt.layer == layer.name);
+            (Some(layer), points.collect())
+        })
+        .collect();
+    let unlisted = document
+        .points
+        .iter()
+        .filter(|pt| persistence::layer_of(&document.layers, pt).is_none());
+    groups.push((None, unlisted.collect()));
+    groups
+}
+
+/// Write elements inside a `<g>` for their layer, or bare if the layer is unknown. Empty groups are skipped.
+fn write_group(
+    svg: &mut String,
+    layer: Option<&Layer>,
+    id_suffix: &str,
+    elements: impl Iterator<Item = String>,
+) {
+    let elements: Vec<String> = elements.collect();
+    if elements.is_empty() {
+        return;
+    }
+    let Some(layer) = layer else {
+        for element in elements {
+            let _ = writeln!(svg, "  {element}");
+        }
+        return;
+    };
+    let opacity = opacity_attribute(layer.opacity.clamp(0.0, 1.0));
+    let _ = writeln!(
+        svg,
+        r#"  <g id="{}{id_suffix}"{opacity}>"#,
+        escape_xml(&layer.name)
+    );
+    for element in elements {
+        let _ = writeln!(svg, "    {element}");
+    }
+    svg.push_str("  </g>\n");
+}
+
+/// An ` opacity="…"` attribute, or nothing when fully opaque.
+fn opacity_attribute(opacity: f32) -> String {
+    if opacity < 1.0 {
+        format!(r#" opacity="{opacity}""#)
+    } else {
+        String::new()
+    }
+}
+
+/// Rough extent of a label, since the viewer's font metrics aren't known.
+#[allow(clippy::cast_precision_loss)]
+fn label_bounds(pt: &Point, label: &str, config: &Config) -> egui::Rect {
+    let (pos, align) = geometry::label_position(pt, config);
+    let size = config.label_font_size;
+    let width = label.chars().count() as f32 * size * LABEL_CHAR_WIDTH;
+    align.anchor_size(pos, egui::vec2(width, size))
+}
+
+/// A point's label as `<text>`, placed as on the canvas. Ids are a debugging aid and aren't exported.
+fn label_element(pt: &Point, config: &Config) -> Option<String> {
+    let label = pt.label.as_ref()?;
+    let (pos, align) = geometry::label_position(pt, config);
+    let anchor = match align.x() {
+        egui::Align::Min => "start",
+        egui::Align::Center => "middle",
+        egui::Align::Max => "end",
+    };
+    let baseline = match align.y() {
+        egui::Align::Min => "hanging",
+        egui::Align::Center => "middle",
+        egui::Align::Max => "auto",
+    };
+    Some(format!(
+        r#"<text x="{}" y="{}" font-size="{}" fill="{}" text-anchor="{anchor}" dominant-baseline="{baseline}">{}</text>"#,
+        pos.x,
+        pos.y,
+        config.label_font_size,
+        config.label_color,
+        escape_xml(label),
+    ))
+}
+
+/// Escape text for use in SVG content or attribute values.
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// A path as an SVG `<path>`, filled like the canvas when closed.
+#[must_use]
+pub fn path_element(path: &Path, points: &[Point], config: &Config) -> String {
+    let vertices = geometry::path_vertices(path, points);
+    let mut data: Vec<String> = vertices
+        .iter()
+        .enumerate()
+        .map(|(i, p)| format!("{} {} {}", if i == 0 { "M" } else { "L" }, p.x, p.y))
+        .collect();
+    let color = &config.path_color;
+    let fill = if path.closed {
+        data.push("Z".to_string());
+        format!(
+            r#"fill="{color}" fill-opacity="{}""#,
+            config.path_fill_opacity.clamp(0.0, 1.0)
+        )
+    } else {
+        r#"fill="none""#.to_string()
+    };
+    format!(
+        r#"<path id="{}" d="{}" {fill} stroke="{color}" stroke-width="{}"/>"#,
+        escape_xml(&path.name),
+        data.join(" "),
+        config.path_width
+    )
+}
+
+/// Lines (and arrowheads) for the edges, matching the canvas, as faint as the fainter end's layer.
+fn edge_elements(document: &Document, config: &Config) -> Vec<String> {
+    let by_id: HashMap<u64, &Point> = document.points.iter().map(|pt| (pt.id, pt)).collect();
+    let color = &config.edge_color;
+    let width = config.edge_width;
+    let mut elements = Vec::new();
+    for edge in &document.edges {
+        let (Some(from), Some(to)) = (by_id.get(&edge.from), by_id.get(&edge.to)) else {
+            continue;
+        };
+        let Some([start, end]) = geometry::edge_segment(from, to, config) else {
+            continue;
+        };
+        let head = edge
+            .directed
+            .then(|| geometry::arrowhead(end, end - start, width));
+        let line_end = head.map_or(end, |head| head[1].lerp(head[2], 0.5));
+        let opacity = [from, to]
+            .iter()
+            .filter_map(|pt| persistence::layer_of(&document.layers, pt))
+            .map(|layer| layer.opacity.clamp(0.0, 1.0))
+            .fold(1.0_f32, f32::min);
+        let opacity = opacity_attribute(opacity);
+        elements.push(format!(
+            r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="{color}" stroke-width="{width}"{opacity}/>"#,
+            start.x, start.y, line_end.x, line_end.y
+        ));
+        if let Some(head) = head {
+            let vertices: Vec<String> = head.iter().map(|p| format!("{},{}", p.x, p.y)).collect();
+            elements.push(format!(
+                r#"<polygon points="{}" fill="{color}"{opacity}/>"#,
+                vertices.join(" ")
+            ));
+        }
+    }
+    elements
+}
+
+/// The elements drawing a point's shape: one, except for a ring, which has a band and its
+/// outlines, and nothing when unfilled and unstroked.
+fn shape_elements(pt: &Point, config: &Config) -> Vec<String> {
+    let style = config.style_for(pt);
+    let fill = if style.fill {
+        config.point_color.as_str()
+    } else {
+        "none"
+    };
+    let stroke = if style.stroke_width > 0.0 {
+        format!(
+            r#" stroke="{}" stroke-width="{}""#,
+            escape_xml(&style.stroke_color),
+            style.stroke_width
+        )
+    } else {
+        String::new()
+    };
+    match geometry::primitive(pt, config) {
+        Primitive::Circle { center, radius } => vec![format!(
+            r#"<circle cx="{}" cy="{}" r="{radius}" fill="{fill}"{stroke}/>"#,
+            center.x, center.y
+        )],
+        Primitive::Ring {
+            center,
+            radius,
+            width,
+        } => {
+            let mut elements = Vec::new();
+            if style.fill {
+                elements.push(format!(
+                    r#"<circle cx="{}" cy="{}" r="{radius}" fill="none" stroke="{fill}" stroke-width="{width}"/>"#,
+                    center.x, center.y
+                ));
+            }
+            // Matches the canvas, which leaves hairline bands unoutlined
+            if !stroke.is_empty() && width > 2.0 * style.stroke_width {
+                for edge in [radius - width / 2.0, radius + width / 2.0] {
+                    elements.push(format!(
+                        r#"<circle cx="{}" cy="{}" r="{edge}" fill="none"{stroke}/>"#,
+                        center.x, center.y
+                    ));
+                }
+            }
+            elements
+        }
+        Primitive::Polygon { vertices, .. } => {
+            let vertices: Vec<String> = vertices
+                .into_iter()
+                .map(|p| format!("{},{}", p.x, p.y))
+                .collect();
+            vec![format!(
+                r#"<polygon points="{}" fill="{fill}"{stroke}/>"#,
+                vertices.join(" ")
+            )]
+        }
+    }
+}
+
+pub fn save_svg(document: &Document, config: &Config) {
+    let _ = fs::write(SVG_FILE, to_svg(document, config));
+}
@@ -0,0 +1,660 @@
+//! Shape geometry shared by rendering, hit testing, snapping and export.
+//!
+//! Each point shape has one [`ShapeGeometry`] implementation. Drawing and the SVG
+//! exporter render its [`Primitive`], while selection and snapping use its bounds
+//! and containment test, so they all agree on where a shape is.
+
+use crate::config::{Config, LabelAnchor};
+use crate::persistence::{Path, Point, PointShape};
+use eframe::egui;
+use std::collections::HashMap;
+
+/// Width of a ring's band, as a fraction of the point radius.
+pub const RING_WIDTH: f32 = 0.35;
+/// Radius of a star's inner vertices, as a fraction of the point radius.
+pub const STAR_INNER_RADIUS: f32 = 0.45;
+/// Half the width of a cross's arms, as a fraction of the point radius.
+pub const CROSS_ARM_WIDTH: f32 = 1.0 / 3.0;
+
+/// Segments used to approximate round outlines.
+const CIRCLE_SEGMENTS: u32 = 32;
+const SEMICIRCLE_SEGMENTS: u32 = 16;
+
+/// Where a shape sits on the canvas: its centre, rotation in radians, and radius.
+#[derive(Clone, Copy)]
+pub struct Placement {
+    pub pos: egui::Pos2,
+    pub rotation: f32,
+    pub radius: f32,
+}
+
+impl Placement {
+    #[must_use]
+    pub fn of(pt: &Point, config: &Config) -> Self {
+        Self {
+            pos: egui::pos2(pt.x, pt.y),
+            rotation: pt.rotation,
+            radius: config.point_radius,
+        }
+    }
+
+    /// Scale a unit outline offset by the radius, rotate it, and move it to the centre.
+    #[must_use]
+    pub fn place(&self, offset: egui::Vec2) -> egui::Pos2 {
+        let offset = offset * self.radius;
+        let (sin, cos) = self.rotation.sin_cos();
+        self.pos
+            + egui::vec2(
+                offset.x * cos - offset.y * sin,
+                offset.x * sin + offset.y * cos,
+            )
+    }
+}
+
+/// What to paint for a placed shape, in canvas coordinates.
+pub enum Primitive {
+    Circle {
+        center: egui::Pos2,
+        radius: f32,
+    },
+    /// A stroked circle; `radius` is the middle of the band.
+    Ring {
+        center: egui::Pos2,
+        radius: f32,
+        width: f32,
+    },
+    /// A filled polygon; concave outlines need triangulating rather than a convex fill.
+    Polygon {
+        vertices: Vec<egui::Pos2>,
+        convex: bool,
+    },
+}
+
+/// The geometry of one kind of point shape.
+///
+/// Only [`unit_outline`](Self::unit_outline) is required; the rest derive from
+/// it and are overridden where a shape has an exact form, such as a circle.
+pub trait ShapeGeometry {
+    /// Outline vertices around the origin at radius 1, before rotation.
+    fn unit_outline(&self) -> Vec<egui::Vec2>;
+
+    /// Whether the outline is convex, so it can be filled without triangulating.
+    fn is_convex(&self) -> bool {
+        false
+    }
+
+    /// The outline placed on the canvas.
+    fn outline(&self, at: &Placement) -> Vec<egui::Pos2> {
+        self.unit_outline()
+            .into_iter()
+            .map(|offset| at.place(offset))
+            .collect()
+    }
+
+    /// Axis-aligned bounding box of the placed shape.
+    fn bounds(&self, at: &Placement) -> egui::Rect {
+        egui::Rect::from_points(&self.outline(at))
+    }
+
+    /// Whether a canvas position lies inside the placed shape.
+    fn contains(&self, at: &Placement, pos: egui::Pos2) -> bool {
+        polygon_contains(&self.outline(at), pos)
+    }
+
+    fn primitive(&self, at: &Placement) -> Primitive {
+        Primitive::Polygon {
+            vertices: self.outline(at),
+            convex: self.is_convex(),
+        }
+    }
+}
+
+/// Vertices of a regular polygon around the origin, starting from angle `start`.
+#[allow(clippy::cast_precision_loss)]
+fn regular(sides: u32, radius: f32, start: f32) -> Vec<egui::Vec2> {
+    (0..sides)
+        .map(|i| {
+            let angle = start + std::f32::consts::TAU * i as f32 / sides as f32;
+            egui::vec2(radius * angle.cos(), radius * angle.sin())
+        })
+        .collect()
+}
+
+/// Pointing up on screen, where y grows downwards.
+const UP: f32 = -std::f32::consts::FRAC_PI_2;
+
+fn disc_bounds(at: &Placement) -> egui::Rect {
+    egui::Rect::from_center_size(at.pos, egui::Vec2::splat(2.0 * at.radius))
+}
+
+pub struct Circle;
+
+impl ShapeGeometry for Circle {
+    fn unit_outline(&self) -> Vec<egui::Vec2> {
+        regular(CIRCLE_SEGMENTS, 1.0, 0.0)
+    }
+
+    fn is_convex(&self) -> bool {
+        true
+    }
+
+    fn bounds(&self, at: &Placement) -> egui::Rect {
+        disc_bounds(at)
+    }
+
+    fn contains(&self, at: &Placement, pos: egui::Pos2) -> bool {
+        at.pos.distance(pos) <= at.radius
+    }
+
+    fn primitive(&self, at: &Placement) -> Primitive {
+        Primitive::Circle {
+            center: at.pos,
+            radius: at.radius,
+        }
+    }
+}
+
+/// A hollow circle. Its hole counts as inside, so a ring can be grabbed by its middle.
+pub struct Ring;
+
+impl ShapeGeometry for Ring {
+    fn unit_outline(&self) -> Vec<egui::Vec2> {
+        Circle.unit_outline()
+    }
+
+    fn bounds(&self, at: &Placement) -> egui::Rect {
+        disc_bounds(at)
+    }
+
+    fn contains(&self, at: &Placement, pos: egui::Pos2) -> bool {
+        Circle.contains(at, pos)
+    }
+
+    fn primitive(&self, at: &Placement) -> Primitive {
+        let width = at.radius * RING_WIDTH;
+        Primitive::Ring {
+            center: at.pos,
+            radius: at.radius - width / 2.0,
+            width,
+        }
+    }
+}
+
+pub struct Square;
+
+impl ShapeGeometry for Square {
+    fn unit_outline(&self) -> Vec<egui::Vec2> {
+        vec![
+            egui::vec2(-1.0, -1.0),
+            egui::vec2(1.0, -1.0),
+            egui::vec2(1.0, 1.0),
+            egui::vec2(-1.0, 1.0),
+        ]
+    }
+
+    fn is_convex(&self) -> bool {
+        true
+    }
+}
+
+/// A square rotated 45°.
+pub struct Diamond;
+
+impl ShapeGeometry for Diamond {
+    fn unit_outline(&self) -> Vec<egui::Vec2> {
+        regular(4, 1.0, UP)
+    }
+
+    fn is_convex(&self) -> bool {
+        true
+    }
+}
+
+/// The upper half of a circle, flat side down.
+pub struct Semicircle;
+
+impl ShapeGeometry for Semicircle {
+    #[allow(clippy::cast_precision_loss)]
+    fn unit_outline(&self) -> Vec<egui::Vec2> {
+        (0..=SEMICIRCLE_SEGMENTS)
+            .map(|i| {
+                let angle = std::f32::consts::PI * i as f32 / SEMICIRCLE_SEGMENTS as f32;
+                egui::vec2(angle.cos(), -angle.sin())
+            })
+            .collect()
+    }
+
+    fn is_convex(&self) -> bool {
+        true
+    }
+}
+
+/// A regular polygon with one vertex pointing up; three sides is the triangle.
+pub struct RegularPolygon {
+    pub sides: u8,
+}
+
+impl ShapeGeometry for RegularPolygon {
+    fn unit_outline(&self) -> Vec<egui::Vec2> {
+        regular(u32::from(self.sides.max(3)), 1.0, UP)
+    }
+
+    fn is_convex(&self) -> bool {
+        true
+    }
+}
+
+/// A five-pointed star.
+pub struct Star;
+
+impl ShapeGeometry for Star {
+    fn unit_outline(&self) -> Vec<egui::Vec2> {
+        let outer = regular(5, 1.0, UP);
+        let inner = regular(5, STAR_INNER_RADIUS, UP + std::f32::consts::PI / 5.0);
+        outer
+            .into_iter()
+            .zip(inner)
+            .flat_map(|(o, i)| [o, i])
+            .collect()
+    }
+}
+
+/// A plus-shaped cross.
+pub struct Cross;
+
+impl ShapeGeometry for Cross {
+    fn unit_outline(&self) -> Vec<egui::Vec2> {
+        let w = CROSS_ARM_WIDTH;
+        vec![
+            egui::vec2(-w, -1.0),
+            egui::vec2(w, -1.0),
+            egui::vec2(w, -w),
+            egui::vec2(1.0, -w),
+            egui::vec2(1.0, w),
+            egui::vec2(w, w),
+            egui::vec2(w, 1.0),
+            egui::vec2(-w, 1.0),
+            egui::vec2(-w, w),
+            egui::vec2(-1.0, w),
+            egui::vec2(-1.0, -w),
+            egui::vec2(-w, -w),
+        ]
+    }
+}
+
+/// A shape from the config's `custom_shapes` table.
+pub struct Custom {
+    pub outline: Vec<egui::Vec2>,
+}
+
+impl ShapeGeometry for Custom {
+    fn unit_outline(&self) -> Vec<egui::Vec2> {
+        self.outline.clone()
+    }
+}
+
+/// A custom shape the config doesn't define: a thin circle marks where it is.
+pub struct Undefined;
+
+impl ShapeGeometry for Undefined {
+    fn unit_outline(&self) -> Vec<egui::Vec2> {
+        Circle.unit_outline()
+    }
+
+    fn bounds(&self, at: &Placement) -> egui::Rect {
+        disc_bounds(at)
+    }
+
+    fn contains(&self, at: &Placement, pos: egui::Pos2) -> bool {
+        Circle.contains(at, pos)
+    }
+
+    fn primitive(&self, at: &Placement) -> Primitive {
+        Primitive::Ring {
+            center: at.pos,
+            radius: at.radius,
+            width: 1.0,
+        }
+    }
+}
+
+/// The geometry implementation for a shape, looking custom shapes up in the config.
+#[must_use]
+pub fn of(shape: &PointShape, config: &Config) -> Box<dyn ShapeGeometry> {
+    match shape {
+        PointShape::Circle => Box::new(Circle),
+        PointShape::Ring => Box::new(Ring),
+        PointShape::Square => Box::new(Square),
+        PointShape::Diamond => Box::new(Diamond),
+        PointShape::Semicircle => Box::new(Semicircle),
+        PointShape::Triangle => Box::new(RegularPolygon { sides: 3 }),
+        PointShape::Polygon(sides) => Box::new(RegularPolygon { sides: *sides }),
+        PointShape::Star => Box::new(Star),
+        PointShape::Cross => Box::new(Cross),
+        PointShape::Custom(name) => match config.custom_outline(name) {
+            Some(outline) => Box::new(Custom { outline }),
+            None => Box::new(Undefined),
+        },
+    }
+}
+
+/// Bounding box of a point's shape on the canvas.
+#[must_use]
+pub fn bounds(pt: &Point, config: &Config) -> egui::Rect {
+    of(&pt.shape, config).bounds(&Placement::of(pt, config))
+}
+
+/// Whether a canvas position lies inside a point's shape.
+#[must_use]
+pub fn contains(pt: &Point, config: &Config, pos: egui::Pos2) -> bool {
+    of(&pt.shape, config).contains(&Placement::of(pt, config), pos)
+}
+
+/// What to paint for a point.
+#[must_use]
+pub fn primitive(pt: &Point, config: &Config) -> Primitive {
+    of(&pt.shape, config).primitive(&Placement::of(pt, config))
+}
+
+/// Even-odd test of whether `pos` is inside the polygon `outline`.
+#[must_use]
+pub fn polygon_contains(outline: &[egui::Pos2], pos: egui::Pos2) -> bool {
+    let mut inside = false;
+    let mut prev = match outline.last() {
+        Some(last) => *last,
+        None => return false,
+    };
+    for &vertex in outline {
+        if (vertex.y > pos.y) != (prev.y > pos.y) {
+            let crossing_x =
+                vertex.x + (pos.y - vertex.y) / (prev.y - vertex.y) * (prev.x - vertex.x);
+            if pos.x < crossing_x {
+                inside = !inside;
+            }
+        }
+        prev = vertex;
+    }
+    inside
+}
+
+/// Length of an edge's arrowhead, as a multiple of the edge width.
+pub const ARROW_SCALE: f32 = 5.0;
+
+/// Where the line from a point's centre towards `target` last leaves its shape.
+///
+/// Returns the centre itself if `target` is inside the shape.
+#[must_use]
+pub fn boundary_towards(pt: &Point, config: &Config, target: egui::Pos2) -> egui::Pos2 {
+    let center = egui::pos2(pt.x, pt.y);
+    let ray = target - center;
+    let outline = of(&pt.shape, config).outline(&Placement::of(pt, config));
+    let mut exit = 0.0_f32;
+    let mut prev = match outline.last() {
+        Some(last) => *last,
+        None => return center,
+    };
+    for &vertex in &outline {
+        let side = vertex - prev;
+        let denom = ray.x * side.y - ray.y * side.x;
+        if denom.abs() > f32::EPSILON {
+            let offset = prev - center;
+            let t = (offset.x * side.y - offset.y * side.x) / denom;
+            let u = (offset.x * ray.y - offset.y * ray.x) / denom;
+            if (0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u) {
+                exit = exit.max(t);
+            }
+        }
+        prev = vertex;
+    }
+    center + ray * exit
+}
+
+/// The visible part of an edge, from the outline of one point to the other's,
+/// or `None` if the shapes overlap so that nothing shows between them.
+#[must_use]
+pub fn edge_segment(from: &Point, to: &Point, config: &Config) -> Option<[egui::Pos2; 2]> {
+    let start = boundary_towards(from, config, egui::pos2(to.x, to.y));
+    let end = boundary_towards(to, config, egui::pos2(from.x, from.y));
+    let direction = egui::vec2(to.x - from.x, to.y - from.y);
+    ((end - start).dot(direction) > 0.0).then_some([start, end])
+}
+
+/// An arrowhead triangle with its tip at `tip`, pointing along `direction`.
+#[must_use]
+pub fn arrowhead(tip: egui::Pos2, direction: egui::Vec2, width: f32) -> [egui::Pos2; 3] {
+    let length = width * ARROW_SCALE;
+    let along = direction.normalized() * length;
+    let across = along.rot90() * 0.5;
+    [tip, tip - along + across, tip - along - across]
+}
+
+/// Centres of a path's points in order, skipping ids with no point.
+#[must_use]
+pub fn path_vertices(path: &Path, points: &[Point]) -> Vec<egui::Pos2> {
+    let by_id: HashMap<u64, &Point> = points.iter().map(|pt| (pt.id, pt)).collect();
+    path.points
+        .iter()
+        .filter_map(|id| by_id.get(id).map(|pt| egui::pos2(pt.x, pt.y)))
+        .collect()
+}
+
+/// Gap between a point's edge and its label.
+pub const LABEL_GAP: f32 = 4.0;
+
+/// The text shown beside a point: its label, prefixed by its id when `show_ids` is on.
+#[must_use]
+pub fn label_text(pt: &Point, show_ids: bool) -> Option<String> {
+    match (&pt.label, show_ids) {
+        (Some(label), true) => Some(format!("#{} {label}", pt.id)),
+        (None, true) => Some(format!("#{}", pt.id)),
+        (Some(label), false) => Some(label.clone()),
+        (None, false) => None,
+    }
+}
+
+/// Where to draw a point's label, and which part of the text goes there.
+#[must_use]
+pub fn label_position(pt: &Point, config: &Config) -> (egui::Pos2, egui::Align2) {
+    let bounds = bounds(pt, config);
+    let center = egui::pos2(pt.x, pt.y);
+    match config.label_anchor {
+        LabelAnchor::Right => (
+            egui::pos2(bounds.max.x + LABEL_GAP, center.y),
+            egui::Align2::LEFT_CENTER,
+        ),
+        LabelAnchor::Left => (
+            egui::pos2(bounds.min.x - LABEL_GAP, center.y),
+            egui::Align2::RIGHT_CENTER,
+        ),
+        LabelAnchor::Above => (
+            egui::pos2(center.x, bounds.min.y - LABEL_GAP),
+            egui::Align2::CENTER_BOTTOM,
+        ),
+        LabelAnchor::Below => (
+            egui::pos2(center.x, bounds.max.y + LABEL_GAP),
+            egui::Align2::CENTER_TOP,
+        ),
+        LabelAnchor::Center => (center, egui::Align2::CENTER_CENTER),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{boundary_towards, bounds, contains, edge_segment, polygon_contains};
+    use crate::config::Config;
+    use crate::persistence::{Point, PointShape};
+    use eframe::egui::{pos2, Pos2, Rect};
+    use std::f32::consts::{FRAC_PI_4, PI};
+
+    fn config() -> Config {
+        Config {
+            point_radius: 10.0,
+            ..Config::default()
+        }
+    }
+
+    fn point(shape: PointShape, x: f32, y: f32, rotation: f32) -> Point {
+        let mut pt = crate::persistence::default_points().remove(0);
+        pt.shape = shape;
+        pt.x = x;
+        pt.y = y;
+        pt.rotation = rotation;
+        pt
+    }
+
+    fn assert_near(actual: Pos2, expected: Pos2) {
+        assert!(
+            actual.distance(expected) < 1e-3,
+            "{actual:?} != {expected:?}"
+        );
+    }
+
+    fn assert_rect(actual: Rect, min: Pos2, max: Pos2) {
+        assert!(
+            actual.min.distance(min) < 1e-3 && actual.max.distance(max) < 1e-3,
+            "{actual:?} != {min:?} - {max:?}"
+        );
+    }
+
+    #[test]
+    fn bounds_of_each_shape() {
+        let config = config();
+        let half_root3 = 10.0 * 3.0_f32.sqrt() / 2.0;
+        let cases = [
+            (PointShape::Circle, pos2(90.0, 90.0), pos2(110.0, 110.0)),
+            (PointShape::Ring, pos2(90.0, 90.0), pos2(110.0, 110.0)),
+            (PointShape::Square, pos2(90.0, 90.0), pos2(110.0, 110.0)),
+            (PointShape::Diamond, pos2(90.0, 90.0), pos2(110.0, 110.0)),
+            (PointShape::Semicircle, pos2(90.0, 90.0), pos2(110.0, 100.0)),
+            (
+                PointShape::Triangle,
+                pos2(100.0 - half_root3, 90.0),
+                pos2(100.0 + half_root3, 105.0),
+            ),
+            (PointShape::Polygon(4), pos2(90.0, 90.0), pos2(110.0, 110.0)),
+            (PointShape::Cross, pos2(90.0, 90.0), pos2(110.0, 110.0)),
+        ];
+        for (shape, min, max) in cases {
+            assert_rect(bounds(&point(shape, 100.0, 100.0, 0.0), &config), min, max);
+        }
+        let star = bounds(&point(PointShape::Star, 100.0, 100.0, 0.0), &config);
+        assert!((star.min.y - 90.0).abs() < 1e-3 && star.max.y < 110.0);
+    }
+
+    #[test]
+    fn rotation_turns_bounds() {
+        let config = config();
+        let diagonal = 10.0 * 2.0_f32.sqrt();
+        let square = point(PointShape::Square, 0.0, 0.0, FRAC_PI_4);
+        assert_rect(
+            bounds(&square, &config),
+            pos2(-diagonal, -diagonal),
+            pos2(diagonal, diagonal),
+        );
+        let diamond = point(PointShape::Diamond, 0.0, 0.0, FRAC_PI_4);
+        let half = diagonal / 2.0;
+        assert_rect(
+            bounds(&diamond, &config),
+            pos2(-half, -half),
+            pos2(half, half),
+        );
+        // Turned upside down, the flat side is on top
+        let semicircle = point(PointShape::Semicircle, 0.0, 0.0, PI);
+        assert_rect(
+            bounds(&semicircle, &config),
+            pos2(-10.0, 0.0),
+            pos2(10.0, 10.0),
+        );
+        // Circles look the same however they are turned
+        let circle = point(PointShape::Circle, 0.0, 0.0, 1.0);
+        assert_rect(
+            bounds(&circle, &config),
+            pos2(-10.0, -10.0),
+            pos2(10.0, 10.0),
+        );
+    }
+
+    #[test]
+    fn contains_follows_each_outline() {
+        let config = config();
+        let inside = |shape: PointShape, rotation: f32, pos: Pos2| {
+            contains(&point(shape, 0.0, 0.0, rotation), &config, pos)
+        };
+        for shape in PointShape::ALL {
+            assert!(inside(shape, 0.0, Pos2::ZERO));
+        }
+        // Within the square's corner, but off the circle, the cross's arms and the diamond
+        let corner = pos2(8.0, 8.0);
+        assert!(inside(PointShape::Square, 0.0, corner));
+        assert!(!inside(PointShape::Circle, 0.0, corner));
+        assert!(!inside(PointShape::Cross, 0.0, corner));
+        assert!(!inside(PointShape::Diamond, 0.0, corner));
+        // A ring's hole still counts, so it can be grabbed by the middle
+        assert!(inside(PointShape::Ring, 0.0, pos2(1.0, 1.0)));
+        assert!(!inside(PointShape::Ring, 0.0, pos2(11.0, 0.0)));
+        // The semicircle is the upper half, until turned over
+        assert!(inside(PointShape::Semicircle, 0.0, pos2(0.0, -5.0)));
+        assert!(!inside(PointShape::Semicircle, 0.0, pos2(0.0, 5.0)));
+        assert!(inside(PointShape::Semicircle, PI, pos2(0.0, 5.0)));
+        // Between two of the star's tips, straight down from its centre
+        assert!(!inside(PointShape::Star, 0.0, pos2(0.0, 6.0)));
+        assert!(inside(PointShape::Star, 0.0, pos2(0.0, -9.0)));
+        // A square turned 45° reaches further along the axes, but not into its old corners
+        assert!(inside(PointShape::Square, FRAC_PI_4, pos2(13.0, 0.0)));
+        assert!(!inside(PointShape::Square, FRAC_PI_4, corner));
+    }
+
+    #[test]
+    fn polygon_contains_handles_concave_and_empty_outlines() {
+        let l_shape = [
+            pos2(0.0, 0.0),
+            pos2(2.0, 0.0),
+            pos2(2.0, 1.0),
+            pos2(1.0, 1.0),
+            pos2(1.0, 2.0),
+            pos2(0.0, 2.0),
+        ];
+        assert!(polygon_contains(&l_shape, pos2(0.5, 1.5)));
+        assert!(polygon_contains(&l_shape, pos2(1.5, 0.5)));
+        assert!(!polygon_contains(&l_shape, pos2(1.5, 1.5)));
+        assert!(!polygon_contains(&[], Pos2::ZERO));
+    }
+
+    #[test]
+    fn boundary_towards_meets_the_outline() {
+        let config = config();
+        let circle = point(PointShape::Circle, 100.0, 100.0, 0.0);
+        assert_near(
+            boundary_towards(&circle, &config, pos2(200.0, 100.0)),
+            pos2(110.0, 100.0),
+        );
+        let square = point(PointShape::Square, 100.0, 100.0, 0.0);
+        assert_near(
+            boundary_towards(&square, &config, pos2(200.0, 150.0)),
+            pos2(110.0, 105.0),
+        );
+        let turned = point(PointShape::Square, 100.0, 100.0, FRAC_PI_4);
+        let diagonal = 10.0 * 2.0_f32.sqrt();
+        assert_near(
+            boundary_towards(&turned, &config, pos2(100.0, 0.0)),
+            pos2(100.0, 100.0 - diagonal),
+        );
+        // A target inside the shape gives the centre
+        assert_near(
+            boundary_towards(&square, &config, pos2(105.0, 105.0)),
+            pos2(100.0, 100.0),
+        );
+    }
+
+    #[test]
+    fn edge_segments_run_between_outlines() {
+        let config = config();
+        let from = point(PointShape::Circle, 0.0, 0.0, 0.0);
+        let to = point(PointShape::Square, 100.0, 0.0, 0.0);
+        let [start, end] = edge_segment(&from, &to, &config).unwrap();
+        assert_near(start, pos2(10.0, 0.0));
+        assert_near(end, pos2(90.0, 0.0));
+        let overlapping = point(PointShape::Circle, 15.0, 0.0, 0.0);
+        assert!(edge_segment(&from, &overlapping, &config).is_none());
+    }
+}
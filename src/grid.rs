@@ -0,0 +1,459 @@
+//! Grid layouts: the lines drawn for each `GridType`, and the nodes points snap to.
+
+use crate::config::{Config, GridType};
+use eframe::egui::{self, Pos2, Rect};
+use std::f32::consts::{FRAC_PI_3, FRAC_PI_6, TAU};
+
+/// Something to stroke in the grid colour, or the major grid colour.
+pub enum GridLine {
+    Segment {
+        points: [Pos2; 2],
+        major: bool,
+    },
+    Circle {
+        center: Pos2,
+        radius: f32,
+        major: bool,
+    },
+}
+
+/// Height of one row of triangles (or hexagon centres) with sides `spacing` long.
+fn row_height(spacing: f32) -> f32 {
+    spacing * 3.0_f32.sqrt() / 2.0
+}
+
+/// Every multiple of `step` from just below `min` to just above `max`, as the multiplier.
+pub fn steps(min: f32, max: f32, step: f32) -> impl Iterator<Item = f32> {
+    let first = (min / step).floor() - 1.0;
+    let last = (max / step).ceil() + 1.0;
+    std::iter::successors(Some(first), |k| Some(k + 1.0)).take_while(move |&k| k <= last)
+}
+
+/// Whether the `k`th line from the origin is a major one, with majors every `every` lines.
+#[allow(clippy::cast_precision_loss)]
+fn is_major(k: f32, every: u32) -> bool {
+    every > 1 && k.rem_euclid(every as f32) == 0.0
+}
+
+/// The spacing of the grid lines drawn at `zoom`.
+///
+/// Zoomed out, the minor lines drop away and the major lines take their place, a division
+/// at a time, until the lines are at least `grid_min_spacing` screen points apart.
+#[must_use]
+#[allow(clippy::cast_precision_loss)]
+pub fn visible_spacing(config: &Config, zoom: f32) -> f32 {
+    let mut spacing = config.grid_spacing;
+    if spacing <= 0.0 || zoom <= 0.0 {
+        return spacing;
+    }
+    let factor = if config.grid_major_every > 1 {
+        config.grid_major_every as f32
+    } else {
+        2.0
+    };
+    while spacing * zoom < config.grid_min_spacing {
+        spacing *= factor;
+    }
+    spacing
+}
+
+/// The grid lines covering `rect`, `spacing` apart.
+#[must_use]
+pub fn lines(config: &Config, rect: Rect, spacing: f32) -> Vec<GridLine> {
+    if spacing <= 0.0 {
+        return Vec::new();
+    }
+    let every = config.grid_major_every;
+    match config.grid_type {
+        GridType::Square => square_lines(rect, spacing, every),
+        GridType::Isometric => isometric_lines(rect, spacing, every),
+        GridType::Hex => hex_lines(rect, spacing),
+        GridType::Polar => polar_lines(rect, spacing, every, config.polar_spokes),
+    }
+}
+
+fn square_lines(rect: Rect, spacing: f32, every: u32) -> Vec<GridLine> {
+    let vertical = steps(rect.min.x, rect.max.x, spacing).map(|k| {
+        let x = k * spacing;
+        GridLine::Segment {
+            points: [egui::pos2(x, rect.min.y), egui::pos2(x, rect.max.y)],
+            major: is_major(k, every),
+        }
+    });
+    let horizontal = steps(rect.min.y, rect.max.y, spacing).map(|k| {
+        let y = k * spacing;
+        GridLine::Segment {
+            points: [egui::pos2(rect.min.x, y), egui::pos2(rect.max.x, y)],
+            major: is_major(k, every),
+        }
+    });
+    vertical.chain(horizontal).collect()
+}
+
+/// Horizontal lines plus two families at ±60°, meeting at the triangles' corners.
+///
+/// Major lines of all three families meet at the corners of larger triangles.
+fn isometric_lines(rect: Rect, spacing: f32, every: u32) -> Vec<GridLine> {
+    let height = row_height(spacing);
+    let mut lines: Vec<GridLine> = steps(rect.min.y, rect.max.y, height)
+        .map(|k| {
+            let y = k * height;
+            GridLine::Segment {
+                points: [egui::pos2(rect.min.x, y), egui::pos2(rect.max.x, y)],
+                major: is_major(k, every),
+            }
+        })
+        .collect();
+    // Along each sloping line x ∓ y/√3 is constant, and a multiple of the spacing
+    let slope = 1.0 / 3.0_f32.sqrt();
+    for sign in [1.0, -1.0] {
+        let offsets = [rect.min.y, rect.max.y].map(|y| sign * y * slope);
+        let min = rect.min.x - offsets[0].max(offsets[1]);
+        let max = rect.max.x - offsets[0].min(offsets[1]);
+        lines.extend(steps(min, max, spacing).map(|k| {
+            let c = k * spacing;
+            GridLine::Segment {
+                points: [
+                    egui::pos2(c + offsets[0], rect.min.y),
+                    egui::pos2(c + offsets[1], rect.max.y),
+                ],
+                major: is_major(k, every),
+            }
+        }));
+    }
+    lines
+}
+
+/// Pointy-topped hexagons centred on the triangular lattice, which has no major lines.
+fn hex_lines(rect: Rect, spacing: f32) -> Vec<GridLine> {
+    let circumradius = spacing / 3.0_f32.sqrt();
+    let corner = |center: Pos2, angle: f32| center + circumradius * egui::Vec2::angled(angle);
+    let mut lines = Vec::new();
+    for center in lattice_nodes(rect.expand(spacing), spacing) {
+        // Each hexagon draws the three sides facing right and down; its neighbours draw the rest
+        for side in 0..3u8 {
+            let facing = f32::from(side) * FRAC_PI_3;
+            lines.push(GridLine::Segment {
+                points: [
+                    corner(center, facing - FRAC_PI_6),
+                    corner(center, facing + FRAC_PI_6),
+                ],
+                major: false,
+            });
+        }
+    }
+    lines
+}
+
+/// Every `every`th ring is major; the spokes are all minor.
+#[allow(clippy::cast_precision_loss)]
+fn polar_lines(rect: Rect, spacing: f32, every: u32, spokes: u32) -> Vec<GridLine> {
+    let origin = Pos2::ZERO;
+    let nearest = rect.distance_to_pos(origin);
+    let farthest = [
+        rect.left_top(),
+        rect.right_top(),
+        rect.left_bottom(),
+        rect.right_bottom(),
+    ]
+    .into_iter()
+    .map(|corner| corner.distance(origin))
+    .fold(0.0, f32::max);
+    let mut lines: Vec<GridLine> = steps(nearest, farthest, spacing)
+        .filter(|&k| k > 0.0)
+        .map(|k| GridLine::Circle {
+            center: origin,
+            radius: k * spacing,
+            major: is_major(k, every),
+        })
+        .collect();
+    let step = TAU / spokes.max(1) as f32;
+    lines.extend((0..spokes).map(|spoke| {
+        let end = origin + farthest * egui::Vec2::angled(spoke as f32 * step);
+        GridLine::Segment {
+            points: [origin, end],
+            major: false,
+        }
+    }));
+    lines
+}
+
+/// Corners of the isometric grid, which are also the hex grid's centres, within `rect`.
+fn lattice_nodes(rect: Rect, spacing: f32) -> Vec<Pos2> {
+    let height = row_height(spacing);
+    let mut nodes = Vec::new();
+    for row in steps(rect.min.y, rect.max.y, height) {
+        // Odd rows sit half a step across
+        let shift = row.rem_euclid(2.0) * spacing / 2.0;
+        for column in steps(rect.min.x - shift, rect.max.x - shift, spacing) {
+            nodes.push(egui::pos2(column * spacing + shift, row * height));
+        }
+    }
+    nodes
+}
+
+/// The grid node nearest `pos`: a square corner, triangle corner, hexagon centre, or ring and spoke crossing.
+#[must_use]
+pub fn nearest_node(config: &Config, pos: Pos2) -> Pos2 {
+    if config.grid_type == GridType::Hex {
+        nearest_cell_center(config, pos)
+    } else {
+        nearest_corner(config, pos)
+    }
+}
+
+/// The point of `candidates` nearest `pos`, or `pos` itself if there are none.
+fn nearest(pos: Pos2, candidates: impl Iterator<Item = Pos2>) -> Pos2 {
+    candidates
+        .min_by(|a, b| a.distance_sq(pos).total_cmp(&b.distance_sq(pos)))
+        .unwrap_or(pos)
+}
+
+/// Lattice nodes round `pos`, enough to include the corners and centres of the cells it is in.
+fn nodes_around(pos: Pos2, spacing: f32) -> Vec<Pos2> {
+    lattice_nodes(
+        Rect::from_center_size(pos, egui::Vec2::splat(2.0 * spacing)),
+        spacing,
+    )
+}
+
+/// The grid corner nearest `pos`: where lines cross, a hexagon vertex, or where a ring crosses a spoke.
+#[must_use]
+#[allow(clippy::cast_precision_loss)]
+pub fn nearest_corner(config: &Config, pos: Pos2) -> Pos2 {
+    let spacing = config.grid_spacing;
+    if spacing <= 0.0 {
+        return pos;
+    }
+    match config.grid_type {
+        GridType::Square => egui::pos2(
+            (pos.x / spacing).round() * spacing,
+            (pos.y / spacing).round() * spacing,
+        ),
+        GridType::Isometric => nearest(pos, nodes_around(pos, spacing).into_iter()),
+        GridType::Hex => {
+            let circumradius = spacing / 3.0_f32.sqrt();
+            let vertices = nodes_around(pos, spacing).into_iter().flat_map(|center| {
+                (0..6u8).map(move |k| {
+                    let angle = FRAC_PI_6 + f32::from(k) * FRAC_PI_3;
+                    center + circumradius * egui::Vec2::angled(angle)
+                })
+            });
+            nearest(pos, vertices)
+        }
+        GridType::Polar => {
+            let radius = (pos.to_vec2().length() / spacing).round() * spacing;
+            let step = TAU / config.polar_spokes.max(1) as f32;
+            let angle = (pos.y.atan2(pos.x) / step).round() * step;
+            Pos2::ZERO + radius * egui::Vec2::angled(angle)
+        }
+    }
+}
+
+/// The centre of the grid cell nearest `pos`: of a square, triangle or hexagon, or midway
+/// between two rings and two spokes.
+#[must_use]
+#[allow(clippy::cast_precision_loss)]
+pub fn nearest_cell_center(config: &Config, pos: Pos2) -> Pos2 {
+    let spacing = config.grid_spacing;
+    if spacing <= 0.0 {
+        return pos;
+    }
+    let halfway = |value: f32, step: f32| ((value / step - 0.5).round() + 0.5) * step;
+    match config.grid_type {
+        GridType::Square => egui::pos2(halfway(pos.x, spacing), halfway(pos.y, spacing)),
+        GridType::Isometric => {
+            // Each node tops one downward triangle to its right and one upward triangle below it
+            let height = row_height(spacing);
+            let centroids = nodes_around(pos, spacing).into_iter().flat_map(|node| {
+                [
+                    node + egui::vec2(spacing / 2.0, height / 3.0),
+                    node + egui::vec2(0.0, 2.0 * height / 3.0),
+                ]
+            });
+            nearest(pos, centroids)
+        }
+        GridType::Hex => nearest(pos, nodes_around(pos, spacing).into_iter()),
+        GridType::Polar => {
+            let radius = halfway(pos.to_vec2().length(), spacing);
+            let step = TAU / config.polar_spokes.max(1) as f32;
+            let angle = halfway(pos.y.atan2(pos.x), step);
+            Pos2::ZERO + radius * egui::Vec2::angled(angle)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        lines, nearest_cell_center, nearest_corner, nearest_node, row_height, visible_spacing,
+        GridLine,
+    };
+    use crate::config::{Config, GridType};
+    use eframe::egui::{pos2, vec2, Pos2, Rect};
+
+    fn grid(grid_type: GridType) -> Config {
+        Config {
+            grid_type,
+            grid_spacing: 10.0,
+            polar_spokes: 4,
+            ..Config::default()
+        }
+    }
+
+    fn assert_near(actual: Pos2, expected: Pos2) {
+        assert!(
+            actual.distance(expected) < 1e-3,
+            "{actual:?} != {expected:?}"
+        );
+    }
+
+    /// Whether `line` passes within a hair of `pos`.
+    fn passes_through(line: &GridLine, pos: Pos2) -> bool {
+        match *line {
+            GridLine::Segment {
+                points: [from, to], ..
+            } => {
+                let along = to - from;
+                let t = ((pos - from).dot(along) / along.length_sq()).clamp(0.0, 1.0);
+                pos.distance(from + t * along) < 1e-3
+            }
+            GridLine::Circle { center, radius, .. } => (pos.distance(center) - radius).abs() < 1e-3,
+        }
+    }
+
+    /// The lines drawn round `pos` that pass through it.
+    fn lines_through(config: &Config, pos: Pos2) -> Vec<GridLine> {
+        let around = Rect::from_center_size(pos, vec2(40.0, 40.0));
+        lines(config, around, config.grid_spacing)
+            .into_iter()
+            .filter(|line| passes_through(line, pos))
+            .collect()
+    }
+
+    const SAMPLES: [Pos2; 4] = [
+        pos2(23.0, 41.0),
+        pos2(-37.0, 12.0),
+        pos2(58.0, -29.0),
+        pos2(-6.0, -64.0),
+    ];
+
+    #[test]
+    fn snapped_nodes_sit_where_drawn_lines_cross() {
+        for (grid_type, crossing) in [
+            (GridType::Square, 2),
+            (GridType::Isometric, 3),
+            (GridType::Polar, 2),
+        ] {
+            let config = grid(grid_type);
+            for pos in SAMPLES {
+                let node = nearest_node(&config, pos);
+                assert_eq!(lines_through(&config, node).len(), crossing, "{pos:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn polar_nodes_lie_on_a_ring_and_a_spoke() {
+        let config = grid(GridType::Polar);
+        for pos in SAMPLES {
+            let through = lines_through(&config, nearest_node(&config, pos));
+            assert!(through
+                .iter()
+                .any(|line| matches!(line, GridLine::Circle { .. })));
+            assert!(through
+                .iter()
+                .any(|line| matches!(line, GridLine::Segment { .. })));
+        }
+    }
+
+    #[test]
+    fn snapped_hex_centres_are_ringed_by_drawn_sides() {
+        let config = grid(GridType::Hex);
+        let circumradius = 10.0 / 3.0_f32.sqrt();
+        for pos in SAMPLES {
+            let center = nearest_node(&config, pos);
+            assert!(lines_through(&config, center).is_empty());
+            // Each of the six vertices round the centre is the end of a drawn side
+            for k in 0..6u8 {
+                let angle = (30.0 + 60.0 * f32::from(k)).to_radians();
+                let vertex = center + circumradius * vec2(angle.cos(), angle.sin());
+                assert!(!lines_through(&config, vertex).is_empty(), "{vertex:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn square_corners_and_centres() {
+        let config = grid(GridType::Square);
+        assert_near(nearest_corner(&config, pos2(14.0, -6.0)), pos2(10.0, -10.0));
+        assert_near(
+            nearest_cell_center(&config, pos2(14.0, -6.0)),
+            pos2(15.0, -5.0),
+        );
+        assert_near(
+            nearest_cell_center(&config, pos2(-1.0, 1.0)),
+            pos2(-5.0, 5.0),
+        );
+        assert_near(nearest_node(&config, pos2(14.0, -6.0)), pos2(10.0, -10.0));
+    }
+
+    #[test]
+    fn isometric_corners_and_centroids() {
+        let config = grid(GridType::Isometric);
+        let height = row_height(10.0);
+        // The second row sits half a step across
+        assert_near(
+            nearest_corner(&config, pos2(4.0, height)),
+            pos2(5.0, height),
+        );
+        // The downward triangle under the top edge from (0, 0) to (10, 0)
+        let centroid = pos2(5.0, height / 3.0);
+        assert_near(nearest_cell_center(&config, pos2(5.0, 2.0)), centroid);
+    }
+
+    #[test]
+    fn hex_vertices_and_centres() {
+        let config = grid(GridType::Hex);
+        let circumradius = 10.0 / 3.0_f32.sqrt();
+        assert_near(nearest_cell_center(&config, pos2(1.0, -1.0)), Pos2::ZERO);
+        assert_near(nearest_node(&config, pos2(1.0, -1.0)), Pos2::ZERO);
+        // The vertex straight above a centre is 30° + 60°
+        assert_near(
+            nearest_corner(&config, pos2(0.0, circumradius - 0.5)),
+            pos2(0.0, circumradius),
+        );
+    }
+
+    #[test]
+    fn polar_crossings_and_cells() {
+        let config = grid(GridType::Polar);
+        assert_near(nearest_corner(&config, pos2(0.5, 18.0)), pos2(0.0, 20.0));
+        let cell = nearest_cell_center(&config, pos2(12.0, 2.0));
+        assert!((cell.to_vec2().length() - 15.0).abs() < 1e-3);
+        assert!((cell.y.atan2(cell.x).to_degrees() - 45.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn no_spacing_leaves_points_alone() {
+        let config = Config {
+            grid_spacing: 0.0,
+            ..Config::default()
+        };
+        assert_near(nearest_corner(&config, pos2(3.0, 4.0)), pos2(3.0, 4.0));
+        assert_near(nearest_cell_center(&config, pos2(3.0, 4.0)), pos2(3.0, 4.0));
+    }
+
+    #[test]
+    fn spacing_grows_by_major_divisions_when_zoomed_out() {
+        let config = Config {
+            grid_spacing: 10.0,
+            grid_major_every: 5,
+            grid_min_spacing: 8.0,
+            ..Config::default()
+        };
+        assert!((visible_spacing(&config, 1.0) - 10.0).abs() < f32::EPSILON);
+        assert!((visible_spacing(&config, 0.5) - 50.0).abs() < f32::EPSILON);
+        assert!((visible_spacing(&config, 0.01) - 1250.0).abs() < f32::EPSILON);
+    }
+}
@@ -1,8 +1,62 @@
 //! Mouse interaction handlers for different modes.
 
+use crate::drawing::RULER_SIZE;
+use crate::persistence::RulerGuide;
 use crate::{config, persistence, state};
 use eframe::egui;
 
+/// How close, in screen points, the pointer must be to a ruler guide to drag it.
+const GUIDE_GRAB: f32 = 4.0;
+
+/// Drag a new guide out of a ruler, or move an existing one in normal mode; dropping it back on
+/// a ruler deletes it. Returns whether a guide is being dragged, so other handlers can skip it.
+pub fn ruler_guides(
+    state: &mut state::AppState,
+    config: &config::Config,
+    response: &egui::Response,
+) -> bool {
+    let rect = response.rect;
+    let on_top_ruler = |pos: egui::Pos2| pos.y < rect.min.y + RULER_SIZE;
+    let on_left_ruler = |pos: egui::Pos2| pos.x < rect.min.x + RULER_SIZE;
+    if response.drag_started() {
+        if let Some(pos) = response.interact_pointer_pos() {
+            let new_guide = |vertical| RulerGuide {
+                vertical,
+                position: if vertical { pos.x } else { pos.y },
+            };
+            if config.show_rulers && (on_top_ruler(pos) || on_left_ruler(pos)) {
+                // The top ruler gives a horizontal guide, the left a vertical one
+                state.ruler_guides.push(new_guide(!on_top_ruler(pos)));
+                state.dragging_guide = Some(state.ruler_guides.len() - 1);
+            } else {
+                state.dragging_guide = state.ruler_guide_to_drag(pos, GUIDE_GRAB, config);
+            }
+        }
+    }
+    let Some(idx) = state.dragging_guide else {
+        return false;
+    };
+    if let Some(pos) = response.interact_pointer_pos() {
+        let guide = &mut state.ruler_guides[idx];
+        guide.position = if guide.vertical { pos.x } else { pos.y };
+        if response.drag_stopped() && config.show_rulers {
+            let dropped_on_ruler = if guide.vertical {
+                on_left_ruler(pos)
+            } else {
+                on_top_ruler(pos)
+            };
+            if dropped_on_ruler {
+                state.ruler_guides.remove(idx);
+            }
+        }
+    }
+    if response.drag_stopped() {
+        state.dragging_guide = None;
+        persistence::save_document(&state.document());
+    }
+    true
+}
+
 pub fn box_select(state: &mut state::AppState, config: &config::Config, response: &egui::Response) {
     if response.drag_started() {
         if let Some(pos) = response.interact_pointer_pos() {
@@ -20,7 +74,7 @@ pub fn box_select(state: &mut state::AppState, config: &config::Config, response
     if response.drag_stopped() {
         if let (Some(start), Some(end)) = (state.box_select_start, state.box_select_end) {
             let rect = egui::Rect::from_two_pos(start, end);
-            state.select_in_box(rect, config.point_radius);
+            state.select_in_box(rect, config);
         }
         state.box_select_start = None;
         state.box_select_end = None;
@@ -30,37 +84,60 @@ pub fn box_select(state: &mut state::AppState, config: &config::Config, response
 pub fn paintbrush(state: &mut state::AppState, config: &config::Config, response: &egui::Response) {
     if response.clicked() || response.dragged() {
         if let Some(pos) = response.interact_pointer_pos() {
-            state.paint_point(
-                pos,
-                config.point_radius,
-                config.move_step,
-                config.grid_spacing,
-                state.snap_to_grid,
-            );
+            state.paint_point(pos, config, state.snap_to_grid);
         }
     }
 
     if response.drag_stopped() {
         state.last_paint_pos = None;
-        persistence::save_points(&state.points);
+        persistence::save_document(&state.document());
+    }
+}
+
+/// Each click adds a measuring point, snapped to the centre of any point clicked; a right-click
+/// starts over.
+pub fn measure(state: &mut state::AppState, config: &config::Config, response: &egui::Response) {
+    if response.clicked() {
+        if let Some(pos) = response.interact_pointer_pos() {
+            state.add_measure_point(pos, config);
+        }
+    }
+    if response.secondary_clicked() {
+        state.measure.clear();
     }
 }
 
 pub fn normal(state: &mut state::AppState, config: &config::Config, response: &egui::Response) {
     if response.drag_started() {
         if let Some(pos) = response.interact_pointer_pos() {
-            if let Some(idx) = state.point_at_pos(pos, config.point_radius) {
-                let selected_indices = state.selected_indices();
-                if selected_indices.contains(&idx) {
-                    state.dragging = Some(idx);
-                } else {
-                    state.selection = state::Selection::Single(idx);
-                    state.dragging = Some(idx);
-                }
+            // Prefer a selected point anywhere in the stack, e.g. one picked by clicking through
+            let hits = state.points_at_pos(pos, config);
+            let selected_indices = state.selected_indices();
+            if let Some(&idx) = hits.iter().find(|idx| selected_indices.contains(idx)) {
+                state.dragging = Some(idx);
+            } else if let Some(&idx) = hits.first() {
+                state.select_point(idx);
+                state.dragging = Some(idx);
+            } else {
+                // Off every point, an unlocked background image can be dragged into place
+                state.dragging_background = state.background_draggable_at(pos, config);
             }
         }
     }
 
+    if response.dragged() && state.dragging_background {
+        let delta = response.drag_delta();
+        if let Some(background) = &mut state.background {
+            background.x += delta.x;
+            background.y += delta.y;
+        }
+    }
+
+    if response.drag_stopped() && state.dragging_background {
+        state.dragging_background = false;
+        persistence::save_document(&state.document());
+    }
+
     if response.dragged() && state.dragging.is_some() {
         if let Some(pos) = response.interact_pointer_pos() {
             let selected = state.selected_indices();
@@ -77,23 +154,38 @@ pub fn normal(state: &mut state::AppState, config: &config::Config, response: &e
                 }
 
                 if state.snap_to_grid {
-                    state.snap_to_grid(config.grid_spacing, config.point_radius);
+                    state.snap_to_grid(config);
+                }
+                if state.snap_to_points {
+                    state.snap_to_points(config);
+                }
+                if config.snap_to_guides {
+                    state.snap_to_ruler_guides(config);
                 }
             }
         }
     }
 
     if response.drag_stopped() && state.dragging.is_some() {
-        persistence::save_points(&state.points);
+        persistence::save_document(&state.document());
         state.dragging = None;
+        state.guides.clear();
     }
 
     if response.clicked() {
         if let Some(pos) = response.interact_pointer_pos() {
-            if let Some(idx) = state.point_at_pos(pos, config.point_radius) {
-                state.selection = state::Selection::Single(idx);
+            let extend = response.ctx.input(|i| i.modifiers.shift);
+            let target = if extend {
+                state.point_at_pos(pos, config)
             } else {
-                state.selection = state::Selection::None;
+                state.click_target(pos, config)
+            };
+            match target {
+                // Shift-click builds up a selection in order, e.g. for connecting points
+                Some(idx) if extend => state.toggle_selected(idx),
+                Some(idx) => state.select_point(idx),
+                None if extend => {}
+                None => state.selection = state::Selection::None,
             }
         }
     }
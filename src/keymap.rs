@@ -0,0 +1,941 @@
+//! Key chords, the actions they trigger, and the keymap table binding one to the other.
+
+use crate::persistence::PointShape;
+use crate::state::PendingMode;
+use eframe::egui;
+use facet::Facet;
+use std::fmt;
+
+/// Rotation applied by a single rotate command: 45 degrees = π/4 radians.
+pub const ROTATION_STEP: f32 = std::f32::consts::PI / 4.0;
+
+/// Outline width added or removed by a single stroke command.
+pub const STROKE_STEP: f32 = 1.0;
+
+/// A key binding as written in `config.toml`.
+///
+/// `keys` is one chord (`"Ctrl+S"`) or a prefix chord followed by a second
+/// chord (`"S C"`), where the prefix must be bound to a `*-mode` action.
+/// Binding an action of `"none"` removes the default binding for those keys.
+#[derive(Facet, Clone)]
+pub struct KeyBinding {
+    pub keys: String,
+    pub action: String,
+}
+
+const DEFAULT_BINDINGS: &[(&str, &str)] = &[
+    ("B", "box-select"),
+    ("P", "paintbrush"),
+    ("M", "measure"),
+    ("Alt+M", "measure-selection"),
+    ("ArrowLeft", "move-left"),
+    ("ArrowRight", "move-right"),
+    ("ArrowUp", "move-up"),
+    ("ArrowDown", "move-down"),
+    ("Shift+ArrowLeft", "move-large-left"),
+    ("Shift+ArrowRight", "move-large-right"),
+    ("Shift+ArrowUp", "move-large-up"),
+    ("Shift+ArrowDown", "move-large-down"),
+    ("C", "clone-mode"),
+    ("C C", "clone"),
+    ("C ArrowLeft", "clone-left"),
+    ("C ArrowRight", "clone-right"),
+    ("C ArrowUp", "clone-up"),
+    ("C ArrowDown", "clone-down"),
+    ("S", "shape-mode"),
+    ("S C", "shape-circle"),
+    ("S S", "shape-square"),
+    ("S D", "shape-diamond"),
+    ("S H", "shape-semicircle"),
+    ("S T", "shape-triangle"),
+    ("S N", "shape-polygon-6"),
+    ("S A", "shape-star"),
+    ("S P", "shape-cross"),
+    ("S R", "shape-ring"),
+    ("R", "rotate-cw"),
+    ("Shift+R", "rotate-ccw"),
+    ("F", "toggle-fill"),
+    ("G", "toggle-snap"),
+    ("Shift+G", "toggle-point-snap"),
+    ("V", "view-mode"),
+    ("V G", "toggle-grid"),
+    ("V B", "toggle-background"),
+    ("V I", "toggle-ids"),
+    ("V D", "toggle-duplicates"),
+    ("V A", "toggle-axes"),
+    ("V U", "toggle-rulers"),
+    ("V M", "toggle-minimap"),
+    ("V S", "cycle-snap-mode"),
+    ("T", "edit-label"),
+    ("Ctrl+F", "find-label"),
+    ("E", "connect"),
+    ("Shift+E", "connect-directed"),
+    ("Alt+E", "disconnect"),
+    ("]", "raise"),
+    ("[", "lower"),
+    ("Shift+]", "bring-to-front"),
+    ("Shift+[", "send-to-back"),
+    ("Ctrl+G", "group"),
+    ("Ctrl+Shift+G", "ungroup"),
+    ("L", "path-mode"),
+    ("L L", "path-polyline"),
+    ("L P", "path-polygon"),
+    ("L I", "path-insert"),
+    ("L R", "path-reverse"),
+    ("L C", "path-close"),
+    ("L N", "path-next"),
+    ("L X", "path-delete"),
+    ("L E", "path-copy-svg"),
+    ("Y", "layer-mode"),
+    ("Y N", "layer-new"),
+    ("Y ArrowLeft", "layer-previous"),
+    ("Y ArrowRight", "layer-next"),
+    ("Y ArrowUp", "layer-raise"),
+    ("Y ArrowDown", "layer-lower"),
+    ("Y H", "layer-toggle-visible"),
+    ("Y L", "layer-toggle-lock"),
+    ("Y M", "layer-move-selected"),
+    ("Y X", "layer-delete"),
+    ("H", "hide"),
+    ("Shift+H", "unhide-all"),
+    ("K", "lock"),
+    ("Shift+K", "unlock-all"),
+    ("X", "delete"),
+    ("Alt+X", "merge-duplicates"),
+    ("Ctrl+S", "save"),
+    ("Ctrl+O", "load"),
+    ("Ctrl+I", "load-background"),
+    ("Ctrl+R", "reset"),
+    ("Ctrl+E", "export-svg"),
+    ("?", "help"),
+    ("Ctrl+Shift+P", "command-palette"),
+    ("Q", "record-macro"),
+    ("Shift+Q", "play-macro"),
+    ("Escape", "quit"),
+];
+
+/// Headings for the help window and cheat sheet, in display order.
+pub const CATEGORIES: &[&str] = &[
+    "Interaction Modes",
+    "Movement",
+    "Cloning",
+    "Shapes",
+    "Rotation",
+    "Style",
+    "Edges",
+    "Arrange",
+    "Groups",
+    "Paths",
+    "Layers",
+    "Visibility",
+    "Labels",
+    "View",
+    "File",
+    "Macros",
+    "Other",
+];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 4] = [
+        Direction::Left,
+        Direction::Right,
+        Direction::Up,
+        Direction::Down,
+    ];
+
+    /// Unit offset in screen coordinates.
+    #[must_use]
+    pub fn offset(self) -> (f32, f32) {
+        match self {
+            Direction::Left => (-1.0, 0.0),
+            Direction::Right => (1.0, 0.0),
+            Direction::Up => (0.0, -1.0),
+            Direction::Down => (0.0, 1.0),
+        }
+    }
+
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Direction::Left => "left",
+            Direction::Right => "right",
+            Direction::Up => "up",
+            Direction::Down => "down",
+        }
+    }
+}
+
+/// Everything a key binding can do.
+#[derive(Clone, PartialEq)]
+pub enum Action {
+    /// Enter a pending mode, waiting for the second key of a chord.
+    Pending(PendingMode),
+    Move(Direction),
+    MoveLarge(Direction),
+    CloneInPlace,
+    CloneAdjacent(Direction),
+    SetShape(PointShape),
+    RotateClockwise,
+    RotateCounterClockwise,
+    ToggleFill,
+    StrokeWider,
+    StrokeThinner,
+    /// Join the selected points in selection order.
+    Connect,
+    ConnectDirected,
+    Disconnect,
+    /// Draw the selected points over the rest of their layer.
+    BringToFront,
+    SendToBack,
+    /// Draw the selected points one step higher in their layer.
+    Raise,
+    Lower,
+    /// Group the selected points and groups, nesting any existing groups.
+    Group,
+    /// Split the outermost selected groups back into their members.
+    Ungroup,
+    /// Make an open path through the selected points.
+    CreatePolyline,
+    /// Make a closed path through the selected points.
+    CreatePolygon,
+    InsertIntoPath,
+    ReversePath,
+    ToggleClosePath,
+    NextPath,
+    DeletePath,
+    /// Copy the current path to the clipboard as an SVG `<path>` element.
+    CopyPathSvg,
+    /// Add a layer above the others and make it current.
+    NewLayer,
+    PreviousLayer,
+    NextLayer,
+    /// Move the current layer up the stack, drawing it over the ones above.
+    RaiseLayer,
+    LowerLayer,
+    ToggleLayerVisible,
+    ToggleLayerLock,
+    /// Put the selected points on the current layer.
+    MoveToLayer,
+    DeleteLayer,
+    /// Edit the label of the selected point in place.
+    EditLabel,
+    /// Select points by searching their labels.
+    FindLabel,
+    ToggleIds,
+    /// Hide the selected points until `UnhideAll`.
+    HideSelected,
+    UnhideAll,
+    /// Stop the selected points being clicked, dragged or box-selected until `UnlockAll`.
+    LockSelected,
+    UnlockAll,
+    /// Outline points within `duplicate_tolerance` of each other.
+    ToggleDuplicates,
+    /// Keep one point from each set of duplicates.
+    MergeDuplicates,
+    ToggleSnap,
+    /// Switch between snapping point edges, or centres to grid corners or cell centres.
+    CycleSnapMode,
+    /// Snap dragged points into line with other points.
+    TogglePointSnap,
+    ToggleGrid,
+    /// Show the reference image under the grid.
+    ToggleBackground,
+    /// Draw the lines through the canvas origin.
+    ToggleAxes,
+    /// Show the rulers, which guides are dragged out of.
+    ToggleRulers,
+    /// Show the overview of the whole document.
+    ToggleMinimap,
+    ToggleBoxSelect,
+    TogglePaintbrush,
+    ToggleMeasure,
+    /// Report the distance between every pair of selected points.
+    MeasureSelection,
+    Delete,
+    Save,
+    Load,
+    /// Choose the reference image to trace over.
+    LoadBackground,
+    Reset,
+    ExportSvg,
+    ToggleHelp,
+    CommandPalette,
+    /// Start recording into a register, or stop if already recording.
+    RecordMacro,
+    PlayMacro,
+    SaveMacros,
+    Quit,
+}
+
+impl Action {
+    /// Every bindable action, in help window order.
+    #[must_use]
+    pub fn all() -> Vec<Action> {
+        let mut actions = vec![
+            Action::Pending(PendingMode::Clone),
+            Action::Pending(PendingMode::Shape),
+            Action::Pending(PendingMode::View),
+            Action::Pending(PendingMode::Path),
+            Action::Pending(PendingMode::Layer),
+            Action::ToggleBoxSelect,
+            Action::TogglePaintbrush,
+            Action::ToggleMeasure,
+        ];
+        actions.extend(Direction::ALL.map(Action::Move));
+        actions.extend(Direction::ALL.map(Action::MoveLarge));
+        actions.push(Action::CloneInPlace);
+        actions.extend(Direction::ALL.map(Action::CloneAdjacent));
+        actions.extend(PointShape::ALL.map(Action::SetShape));
+        actions.extend([
+            Action::RotateClockwise,
+            Action::RotateCounterClockwise,
+            Action::ToggleFill,
+            Action::StrokeWider,
+            Action::StrokeThinner,
+            Action::Connect,
+            Action::ConnectDirected,
+            Action::Disconnect,
+            Action::Raise,
+            Action::Lower,
+            Action::BringToFront,
+            Action::SendToBack,
+            Action::Group,
+            Action::Ungroup,
+            Action::CreatePolyline,
+            Action::CreatePolygon,
+            Action::InsertIntoPath,
+            Action::ReversePath,
+            Action::ToggleClosePath,
+            Action::NextPath,
+            Action::DeletePath,
+            Action::CopyPathSvg,
+            Action::NewLayer,
+            Action::PreviousLayer,
+            Action::NextLayer,
+            Action::RaiseLayer,
+            Action::LowerLayer,
+            Action::ToggleLayerVisible,
+            Action::ToggleLayerLock,
+            Action::MoveToLayer,
+            Action::DeleteLayer,
+            Action::EditLabel,
+            Action::FindLabel,
+            Action::ToggleSnap,
+            Action::CycleSnapMode,
+            Action::TogglePointSnap,
+            Action::ToggleGrid,
+            Action::ToggleBackground,
+            Action::ToggleAxes,
+            Action::ToggleRulers,
+            Action::ToggleMinimap,
+            Action::ToggleIds,
+            Action::HideSelected,
+            Action::UnhideAll,
+            Action::LockSelected,
+            Action::UnlockAll,
+            Action::ToggleDuplicates,
+            Action::MergeDuplicates,
+            Action::MeasureSelection,
+            Action::Save,
+            Action::Load,
+            Action::LoadBackground,
+            Action::Reset,
+            Action::ExportSvg,
+            Action::RecordMacro,
+            Action::PlayMacro,
+            Action::SaveMacros,
+            Action::Delete,
+            Action::ToggleHelp,
+            Action::CommandPalette,
+            Action::Quit,
+        ]);
+        actions
+    }
+
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Action> {
+        // Any polygon can be bound, not just the hexagon listed in `all`
+        if let Some(shape) = name.strip_prefix("shape-").and_then(PointShape::from_name) {
+            return Some(Action::SetShape(shape));
+        }
+        Self::all().into_iter().find(|action| action.name() == name)
+    }
+
+    /// Identifier used for this action in the `keymap` config table.
+    #[must_use]
+    pub fn name(&self) -> String {
+        match self {
+            Action::Pending(mode) => match mode {
+                PendingMode::None => "none".to_string(),
+                PendingMode::Clone => "clone-mode".to_string(),
+                PendingMode::Shape => "shape-mode".to_string(),
+                PendingMode::View => "view-mode".to_string(),
+                PendingMode::Path => "path-mode".to_string(),
+                PendingMode::Layer => "layer-mode".to_string(),
+                PendingMode::Record => "record-mode".to_string(),
+                PendingMode::Play => "play-mode".to_string(),
+            },
+            Action::Move(dir) => format!("move-{}", dir.name()),
+            Action::MoveLarge(dir) => format!("move-large-{}", dir.name()),
+            Action::CloneInPlace => "clone".to_string(),
+            Action::CloneAdjacent(dir) => format!("clone-{}", dir.name()),
+            Action::SetShape(shape) => format!("shape-{}", shape.name()),
+            Action::RotateClockwise => "rotate-cw".to_string(),
+            Action::RotateCounterClockwise => "rotate-ccw".to_string(),
+            Action::ToggleFill => "toggle-fill".to_string(),
+            Action::StrokeWider => "stroke-wider".to_string(),
+            Action::StrokeThinner => "stroke-thinner".to_string(),
+            Action::Connect => "connect".to_string(),
+            Action::ConnectDirected => "connect-directed".to_string(),
+            Action::Disconnect => "disconnect".to_string(),
+            Action::BringToFront => "bring-to-front".to_string(),
+            Action::SendToBack => "send-to-back".to_string(),
+            Action::Raise => "raise".to_string(),
+            Action::Lower => "lower".to_string(),
+            Action::Group => "group".to_string(),
+            Action::Ungroup => "ungroup".to_string(),
+            Action::CreatePolyline => "path-polyline".to_string(),
+            Action::CreatePolygon => "path-polygon".to_string(),
+            Action::InsertIntoPath => "path-insert".to_string(),
+            Action::ReversePath => "path-reverse".to_string(),
+            Action::ToggleClosePath => "path-close".to_string(),
+            Action::NextPath => "path-next".to_string(),
+            Action::DeletePath => "path-delete".to_string(),
+            Action::CopyPathSvg => "path-copy-svg".to_string(),
+            Action::NewLayer => "layer-new".to_string(),
+            Action::PreviousLayer => "layer-previous".to_string(),
+            Action::NextLayer => "layer-next".to_string(),
+            Action::RaiseLayer => "layer-raise".to_string(),
+            Action::LowerLayer => "layer-lower".to_string(),
+            Action::ToggleLayerVisible => "layer-toggle-visible".to_string(),
+            Action::ToggleLayerLock => "layer-toggle-lock".to_string(),
+            Action::MoveToLayer => "layer-move-selected".to_string(),
+            Action::DeleteLayer => "layer-delete".to_string(),
+            Action::ToggleSnap => "toggle-snap".to_string(),
+            Action::CycleSnapMode => "cycle-snap-mode".to_string(),
+            Action::TogglePointSnap => "toggle-point-snap".to_string(),
+            Action::ToggleGrid => "toggle-grid".to_string(),
+            Action::ToggleBackground => "toggle-background".to_string(),
+            Action::ToggleAxes => "toggle-axes".to_string(),
+            Action::ToggleRulers => "toggle-rulers".to_string(),
+            Action::ToggleMinimap => "toggle-minimap".to_string(),
+            Action::EditLabel => "edit-label".to_string(),
+            Action::FindLabel => "find-label".to_string(),
+            Action::ToggleIds => "toggle-ids".to_string(),
+            Action::HideSelected => "hide".to_string(),
+            Action::UnhideAll => "unhide-all".to_string(),
+            Action::LockSelected => "lock".to_string(),
+            Action::UnlockAll => "unlock-all".to_string(),
+            Action::ToggleDuplicates => "toggle-duplicates".to_string(),
+            Action::MergeDuplicates => "merge-duplicates".to_string(),
+            Action::ToggleBoxSelect => "box-select".to_string(),
+            Action::TogglePaintbrush => "paintbrush".to_string(),
+            Action::ToggleMeasure => "measure".to_string(),
+            Action::MeasureSelection => "measure-selection".to_string(),
+            Action::Delete => "delete".to_string(),
+            Action::Save => "save".to_string(),
+            Action::Load => "load".to_string(),
+            Action::LoadBackground => "load-background".to_string(),
+            Action::Reset => "reset".to_string(),
+            Action::ExportSvg => "export-svg".to_string(),
+            Action::ToggleHelp => "help".to_string(),
+            Action::CommandPalette => "command-palette".to_string(),
+            Action::RecordMacro => "record-macro".to_string(),
+            Action::PlayMacro => "play-macro".to_string(),
+            Action::SaveMacros => "save-macros".to_string(),
+            Action::Quit => "quit".to_string(),
+        }
+    }
+
+    #[must_use]
+    pub fn description(&self) -> String {
+        match self {
+            Action::Pending(mode) => match mode {
+                PendingMode::None => "Nothing".to_string(),
+                PendingMode::Clone => "Clone mode".to_string(),
+                PendingMode::Shape => "Shape mode".to_string(),
+                PendingMode::View => "View mode".to_string(),
+                PendingMode::Path => "Path mode".to_string(),
+                PendingMode::Layer => "Layer mode".to_string(),
+                PendingMode::Record => "Record macro".to_string(),
+                PendingMode::Play => "Play macro".to_string(),
+            },
+            Action::Move(dir) => format!("Move selected point(s) {}", dir.name()),
+            Action::MoveLarge(dir) => {
+                format!("Move selected point(s) {} by large step", dir.name())
+            }
+            Action::CloneInPlace => "Clone on top".to_string(),
+            Action::CloneAdjacent(dir) => format!("Clone adjacent, {}", dir.name()),
+            Action::SetShape(shape) => format!("Set shape to {}", shape.label()),
+            Action::RotateClockwise => "Rotate clockwise (45°)".to_string(),
+            Action::RotateCounterClockwise => "Rotate counter-clockwise (45°)".to_string(),
+            Action::ToggleFill => "Toggle fill of selected point(s)".to_string(),
+            Action::StrokeWider => "Widen outline of selected point(s)".to_string(),
+            Action::StrokeThinner => "Narrow outline of selected point(s)".to_string(),
+            Action::Connect => "Connect selected points in selection order".to_string(),
+            Action::ConnectDirected => {
+                "Connect selected points in selection order, with arrows".to_string()
+            }
+            Action::Disconnect => "Remove edges between selected points".to_string(),
+            Action::BringToFront => "Bring selected point(s) to front".to_string(),
+            Action::SendToBack => "Send selected point(s) to back".to_string(),
+            Action::Raise => "Raise selected point(s) one step".to_string(),
+            Action::Lower => "Lower selected point(s) one step".to_string(),
+            Action::Group => "Group selected point(s)".to_string(),
+            Action::Ungroup => "Ungroup selected group(s)".to_string(),
+            Action::CreatePolyline => "New polyline through selected points".to_string(),
+            Action::CreatePolygon => "New polygon through selected points".to_string(),
+            Action::InsertIntoPath => "Insert selected point(s) into current path".to_string(),
+            Action::ReversePath => "Reverse current path".to_string(),
+            Action::ToggleClosePath => "Open/close current path".to_string(),
+            Action::NextPath => "Select next path".to_string(),
+            Action::DeletePath => "Delete current path".to_string(),
+            Action::CopyPathSvg => "Copy current path as an SVG path element".to_string(),
+            Action::NewLayer => "New layer".to_string(),
+            Action::PreviousLayer => "Select layer below".to_string(),
+            Action::NextLayer => "Select layer above".to_string(),
+            Action::RaiseLayer => "Raise current layer".to_string(),
+            Action::LowerLayer => "Lower current layer".to_string(),
+            Action::ToggleLayerVisible => "Show/hide current layer".to_string(),
+            Action::ToggleLayerLock => "Lock/unlock current layer".to_string(),
+            Action::MoveToLayer => "Move selected point(s) to current layer".to_string(),
+            Action::DeleteLayer => "Delete current layer, keeping its points".to_string(),
+            Action::ToggleSnap => "Toggle snap-to-grid".to_string(),
+            Action::CycleSnapMode => "Cycle grid snap mode: edges, corners, centres".to_string(),
+            Action::TogglePointSnap => "Toggle snapping to other points".to_string(),
+            Action::ToggleGrid => "Toggle grid visibility".to_string(),
+            Action::ToggleBackground => "Toggle background image".to_string(),
+            Action::ToggleAxes => "Toggle origin axes".to_string(),
+            Action::ToggleRulers => "Toggle rulers".to_string(),
+            Action::ToggleMinimap => "Toggle minimap".to_string(),
+            Action::EditLabel => "Edit label of selected point".to_string(),
+            Action::FindLabel => "Find points by label".to_string(),
+            Action::ToggleIds => "Toggle point id display".to_string(),
+            Action::HideSelected => "Hide selected point(s)".to_string(),
+            Action::UnhideAll => "Show all hidden points".to_string(),
+            Action::LockSelected => "Lock selected point(s)".to_string(),
+            Action::UnlockAll => "Unlock all locked points".to_string(),
+            Action::ToggleDuplicates => "Toggle duplicate point highlighting".to_string(),
+            Action::MergeDuplicates => "Merge duplicate points".to_string(),
+            Action::ToggleBoxSelect => {
+                "Toggle box select (arrow keys expand selection)".to_string()
+            }
+            Action::TogglePaintbrush => "Toggle paintbrush (click/drag paints points)".to_string(),
+            Action::ToggleMeasure => {
+                "Toggle measure mode (clicks measure distances and angles)".to_string()
+            }
+            Action::MeasureSelection => "Measure distances between selected points".to_string(),
+            Action::Delete => "Delete selected".to_string(),
+            Action::Save => "Save".to_string(),
+            Action::Load => "Load".to_string(),
+            Action::LoadBackground => "Load background image".to_string(),
+            Action::Reset => "Reset".to_string(),
+            Action::ExportSvg => "Export SVG".to_string(),
+            Action::ToggleHelp => "Show/hide help".to_string(),
+            Action::CommandPalette => "Open command palette".to_string(),
+            Action::RecordMacro => "Record macro into register (press again to stop)".to_string(),
+            Action::PlayMacro => "Play macro from register".to_string(),
+            Action::SaveMacros => "Save macros to config".to_string(),
+            Action::Quit => "Quit".to_string(),
+        }
+    }
+
+    /// Heading this action is listed under, one of [`CATEGORIES`].
+    #[must_use]
+    pub fn category(&self) -> &'static str {
+        match self {
+            Action::Pending(_)
+            | Action::ToggleBoxSelect
+            | Action::TogglePaintbrush
+            | Action::ToggleMeasure => "Interaction Modes",
+            Action::Move(_) | Action::MoveLarge(_) => "Movement",
+            Action::CloneInPlace | Action::CloneAdjacent(_) => "Cloning",
+            Action::SetShape(_) => "Shapes",
+            Action::RotateClockwise | Action::RotateCounterClockwise => "Rotation",
+            Action::ToggleFill | Action::StrokeWider | Action::StrokeThinner => "Style",
+            Action::Connect | Action::ConnectDirected | Action::Disconnect => "Edges",
+            Action::BringToFront | Action::SendToBack | Action::Raise | Action::Lower => "Arrange",
+            Action::Group | Action::Ungroup => "Groups",
+            Action::CreatePolyline
+            | Action::CreatePolygon
+            | Action::InsertIntoPath
+            | Action::ReversePath
+            | Action::ToggleClosePath
+            | Action::NextPath
+            | Action::DeletePath
+            | Action::CopyPathSvg => "Paths",
+            Action::NewLayer
+            | Action::PreviousLayer
+            | Action::NextLayer
+            | Action::RaiseLayer
+            | Action::LowerLayer
+            | Action::ToggleLayerVisible
+            | Action::ToggleLayerLock
+            | Action::MoveToLayer
+            | Action::DeleteLayer => "Layers",
+            Action::EditLabel | Action::FindLabel => "Labels",
+            Action::HideSelected | Action::UnhideAll | Action::LockSelected | Action::UnlockAll => {
+                "Visibility"
+            }
+            Action::ToggleSnap
+            | Action::CycleSnapMode
+            | Action::TogglePointSnap
+            | Action::ToggleGrid
+            | Action::ToggleBackground
+            | Action::ToggleAxes
+            | Action::ToggleRulers
+            | Action::ToggleMinimap
+            | Action::ToggleIds
+            | Action::ToggleDuplicates => "View",
+            Action::Save
+            | Action::Load
+            | Action::LoadBackground
+            | Action::Reset
+            | Action::ExportSvg => "File",
+            Action::RecordMacro | Action::PlayMacro | Action::SaveMacros => "Macros",
+            Action::Delete
+            | Action::MergeDuplicates
+            | Action::MeasureSelection
+            | Action::ToggleHelp
+            | Action::CommandPalette
+            | Action::Quit => "Other",
+        }
+    }
+}
+
+/// A single key press with the modifiers that must be held.
+#[derive(Clone, Copy, PartialEq)]
+pub struct KeyChord {
+    pub key: egui::Key,
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+}
+
+impl KeyChord {
+    /// Parse a chord such as `"G"`, `"Shift+ArrowLeft"` or `"Ctrl+S"`.
+    #[must_use]
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut parts: Vec<&str> = text.split('+').collect();
+        // A trailing empty part means the key itself is `+`
+        let key = match parts.pop()? {
+            "" => egui::Key::Plus,
+            name => egui::Key::from_name(name)?,
+        };
+        let mut chord = KeyChord {
+            key,
+            ctrl: false,
+            shift: false,
+            alt: false,
+        };
+        for modifier in parts.into_iter().filter(|part| !part.is_empty()) {
+            match modifier.to_lowercase().as_str() {
+                "ctrl" | "cmd" | "command" => chord.ctrl = true,
+                "shift" => chord.shift = true,
+                "alt" | "option" => chord.alt = true,
+                _ => return None,
+            }
+        }
+        Some(chord)
+    }
+
+    /// Whether a key press matches, optionally disregarding Shift (so `?` matches Shift+/).
+    #[must_use]
+    pub fn matches(&self, key: egui::Key, modifiers: egui::Modifiers, ignore_shift: bool) -> bool {
+        self.key == key
+            && self.ctrl == modifiers.command
+            && self.alt == modifiers.alt
+            && (self.shift == modifiers.shift || (ignore_shift && !self.shift))
+    }
+}
+
+impl fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.ctrl {
+            write!(f, "Ctrl+")?;
+        }
+        if self.alt {
+            write!(f, "Alt+")?;
+        }
+        if self.shift {
+            write!(f, "Shift+")?;
+        }
+        write!(f, "{}", self.key.symbol_or_name())
+    }
+}
+
+/// A resolved binding: `chord` pressed in `mode` triggers `action`.
+#[derive(Clone)]
+pub struct Binding {
+    pub mode: PendingMode,
+    /// The chord that entered `mode`, if any, for display.
+    pub prefix: Option<KeyChord>,
+    pub chord: KeyChord,
+    pub action: Action,
+}
+
+impl Binding {
+    /// Human readable key sequence, e.g. `C then ⏴`.
+    #[must_use]
+    pub fn keys_label(&self) -> String {
+        match self.prefix {
+            Some(prefix) => format!("{prefix} then {}", self.chord),
+            None => self.chord.to_string(),
+        }
+    }
+
+    fn keys_markdown(&self) -> String {
+        match self.prefix {
+            Some(prefix) => format!("<kbd>{prefix}</kbd> then <kbd>{}</kbd>", self.chord),
+            None => format!("<kbd>{}</kbd>", self.chord),
+        }
+    }
+}
+
+/// The table of active key bindings, built from the defaults plus any config overrides.
+#[derive(Clone)]
+pub struct Keymap {
+    bindings: Vec<Binding>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::new(&[]).0
+    }
+}
+
+impl Keymap {
+    /// Build the keymap, applying `overrides` on top of the default bindings.
+    ///
+    /// Overrides that can't be parsed are skipped and described in the returned errors.
+    ///
+    /// # Panics
+    ///
+    /// Panics if one of the built-in default bindings is invalid.
+    #[must_use]
+    pub fn new(overrides: &[KeyBinding]) -> (Self, Vec<String>) {
+        let mut keymap = Keymap {
+            bindings: Vec::new(),
+        };
+        for (keys, action) in DEFAULT_BINDINGS {
+            keymap
+                .bind(keys, action)
+                .expect("default bindings are valid");
+        }
+        let errors = overrides
+            .iter()
+            .filter_map(|binding| {
+                keymap
+                    .bind(&binding.keys, &binding.action)
+                    .err()
+                    .map(|e| format!("keymap: {e}"))
+            })
+            .collect();
+        (keymap, errors)
+    }
+
+    fn bind(&mut self, keys: &str, action: &str) -> Result<(), String> {
+        let chords = keys
+            .split_whitespace()
+            .map(|chord| {
+                KeyChord::parse(chord).ok_or_else(|| format!("unknown key chord `{chord}`"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let (mode, prefix, chord) = match chords.as_slice() {
+            [chord] => (PendingMode::None, None, *chord),
+            [prefix, chord] => match self.lookup_exact(PendingMode::None, *prefix) {
+                Some(Action::Pending(mode)) => (mode, Some(*prefix), *chord),
+                _ => return Err(format!("`{prefix}` in `{keys}` is not bound to a mode")),
+            },
+            _ => return Err(format!("`{keys}` must be one or two key chords")),
+        };
+
+        self.bindings
+            .retain(|binding| binding.mode != mode || binding.chord != chord);
+        if action == "none" {
+            return Ok(());
+        }
+        let action =
+            Action::from_name(action).ok_or_else(|| format!("unknown action `{action}`"))?;
+        self.bindings.push(Binding {
+            mode,
+            prefix,
+            chord,
+            action,
+        });
+        Ok(())
+    }
+
+    fn lookup_exact(&self, mode: PendingMode, chord: KeyChord) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|binding| binding.mode == mode && binding.chord == chord)
+            .map(|binding| binding.action.clone())
+    }
+
+    /// The action bound to a key press in the given pending mode.
+    #[must_use]
+    pub fn lookup(
+        &self,
+        mode: PendingMode,
+        key: egui::Key,
+        modifiers: egui::Modifiers,
+    ) -> Option<Action> {
+        let find = |ignore_shift: bool| {
+            self.bindings
+                .iter()
+                .find(|b| b.mode == mode && b.chord.matches(key, modifiers, ignore_shift))
+                .map(|b| b.action.clone())
+        };
+        // Keys like `?` are typed with Shift held, but letters with Shift are chords of their own
+        let typed_with_shift = matches!(
+            key,
+            egui::Key::Questionmark | egui::Key::Colon | egui::Key::Pipe | egui::Key::Plus
+        );
+        find(false).or_else(|| {
+            if modifiers.shift && typed_with_shift {
+                find(true)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Key sequence of the first binding that triggers `action`, if it is bound at all.
+    #[must_use]
+    pub fn keys_for(&self, action: &Action) -> Option<String> {
+        self.bindings
+            .iter()
+            .find(|binding| binding.action == *action)
+            .map(Binding::keys_label)
+    }
+
+    /// Bindings grouped under each of [`CATEGORIES`], in action order.
+    #[must_use]
+    pub fn by_category(&self) -> Vec<(&'static str, Vec<&Binding>)> {
+        let actions = Action::all();
+        let position = |binding: &Binding| actions.iter().position(|a| *a == binding.action);
+        CATEGORIES
+            .iter()
+            .map(|category| {
+                let mut bindings: Vec<&Binding> = self
+                    .bindings
+                    .iter()
+                    .filter(|binding| binding.action.category() == *category)
+                    .collect();
+                bindings.sort_by_key(|binding| position(binding));
+                (*category, bindings)
+            })
+            .filter(|(_, bindings)| !bindings.is_empty())
+            .collect()
+    }
+
+    /// A Markdown cheat sheet of the active bindings and their action names, in the style of the README.
+    #[must_use]
+    pub fn cheat_sheet(&self) -> String {
+        let mut lines = Vec::new();
+        for (category, bindings) in self.by_category() {
+            lines.push(format!("### {category}"));
+            for binding in bindings {
+                lines.push(format!(
+                    "- {}: {} (`{}`)",
+                    binding.keys_markdown(),
+                    binding.action.description(),
+                    binding.action.name()
+                ));
+            }
+            lines.push(String::new());
+        }
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Action, KeyBinding, KeyChord, Keymap};
+    use crate::state::PendingMode;
+    use eframe::egui::{Key, Modifiers};
+
+    fn binding(keys: &str, action: &str) -> KeyBinding {
+        KeyBinding {
+            keys: keys.to_string(),
+            action: action.to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_chords_with_modifiers() {
+        let chord = KeyChord::parse("Ctrl+Shift+ArrowLeft").unwrap();
+        assert!(chord.key == Key::ArrowLeft && chord.ctrl && chord.shift && !chord.alt);
+        let chord = KeyChord::parse("option+x").unwrap();
+        assert!(chord.key == Key::X && chord.alt && !chord.ctrl);
+        assert!(KeyChord::parse("Ctrl++").is_some_and(|chord| chord.key == Key::Plus));
+        assert!(KeyChord::parse("?").is_some_and(|chord| chord.key == Key::Questionmark));
+    }
+
+    #[test]
+    fn rejects_unknown_keys_and_modifiers() {
+        assert!(KeyChord::parse("Hyper+A").is_none());
+        assert!(KeyChord::parse("Ctrl+Nope").is_none());
+    }
+
+    #[test]
+    fn chords_display_as_they_parse() {
+        for text in ["G", "Ctrl+S", "Alt+X", "Ctrl+Shift+P"] {
+            let chord = KeyChord::parse(text).unwrap();
+            assert!(KeyChord::parse(&chord.to_string()) == Some(chord), "{text}");
+        }
+    }
+
+    #[test]
+    fn overrides_replace_and_unbind_defaults() {
+        let (keymap, errors) = Keymap::new(&[
+            binding("Ctrl+D", "delete"),
+            binding("C D", "clone-down"),
+            binding("X", "none"),
+        ]);
+        assert!(errors.is_empty());
+        let ctrl = Modifiers::COMMAND;
+        assert!(keymap.lookup(PendingMode::None, Key::D, ctrl) == Some(Action::Delete));
+        assert!(keymap
+            .lookup(PendingMode::None, Key::X, Modifiers::NONE)
+            .is_none());
+        assert!(keymap
+            .lookup(PendingMode::Clone, Key::D, Modifiers::NONE)
+            .is_some_and(|action| action.name() == "clone-down"));
+    }
+
+    #[test]
+    fn reports_invalid_overrides() {
+        let (_, errors) = Keymap::new(&[
+            binding("Ctrl+Nope", "delete"),
+            binding("G D", "delete"),
+            binding("C C C", "clone"),
+            binding("D", "no-such-action"),
+        ]);
+        assert_eq!(errors.len(), 4);
+        assert!(errors[1].contains("not bound to a mode"));
+        assert!(errors[3].contains("unknown action"));
+    }
+
+    #[test]
+    fn only_ignores_shift_for_keys_typed_with_it() {
+        let keymap = Keymap::default();
+        let shift = Modifiers::SHIFT;
+        assert!(
+            keymap.lookup(PendingMode::None, Key::Questionmark, shift) == Some(Action::ToggleHelp)
+        );
+        assert!(
+            keymap.lookup(PendingMode::None, Key::R, shift) == Some(Action::RotateCounterClockwise)
+        );
+        assert!(keymap.lookup(PendingMode::None, Key::X, shift).is_none());
+    }
+}
@@ -2,8 +2,17 @@
 #![allow(clippy::multiple_crate_versions)]
 
 pub mod config;
+pub mod custom_shape;
 pub mod drawing;
+pub mod export;
+pub mod geometry;
+pub mod grid;
 pub mod interactions;
+pub mod keymap;
+pub mod macros;
+pub mod measure;
+pub mod palette;
 pub mod persistence;
+pub mod snap;
 pub mod state;
 pub mod ui;
@@ -0,0 +1,169 @@
+//! Keyboard macros: recorded sequences of actions stored in named registers.
+
+use crate::keymap::Action;
+use std::collections::BTreeMap;
+
+/// One recorded command: an action and the count it was run with.
+#[derive(Clone, PartialEq)]
+pub struct MacroStep {
+    pub action: Action,
+    pub count: u32,
+}
+
+impl MacroStep {
+    /// Parse a step such as `"move-left"` or `"3 clone-down"`.
+    #[must_use]
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut words = text.split_whitespace();
+        let first = words.next()?;
+        let (count, name) = match first.parse::<u32>() {
+            Ok(count) => (count, words.next()?),
+            Err(_) => (1, first),
+        };
+        if words.next().is_some() {
+            return None;
+        }
+        Some(MacroStep {
+            action: Action::from_name(name)?,
+            count,
+        })
+    }
+}
+
+impl std::fmt::Display for MacroStep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.count > 1 {
+            write!(f, "{} ", self.count)?;
+        }
+        write!(f, "{}", self.action.name())
+    }
+}
+
+/// Whether running `action` is captured while recording a macro.
+///
+/// Commands that manage macros or open windows are left out of the recording.
+#[must_use]
+pub fn is_recordable(action: &Action) -> bool {
+    !matches!(
+        action,
+        Action::Pending(_)
+            | Action::RecordMacro
+            | Action::PlayMacro
+            | Action::SaveMacros
+            | Action::CommandPalette
+            | Action::EditLabel
+            | Action::FindLabel
+            | Action::ToggleHelp
+            | Action::Quit
+    )
+}
+
+/// Registers are named by a single lowercase letter, as in vim.
+#[must_use]
+pub fn register_for_key(key: eframe::egui::Key) -> Option<char> {
+    let name = key.name();
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_alphabetic() => Some(c.to_ascii_lowercase()),
+        _ => None,
+    }
+}
+
+/// Parse the `[macros]` config table, mapping registers to comma-separated steps.
+///
+/// Entries that can't be parsed are skipped and described in the returned errors.
+#[must_use]
+pub fn parse_table(
+    table: &BTreeMap<String, String>,
+) -> (BTreeMap<char, Vec<MacroStep>>, Vec<String>) {
+    let mut macros = BTreeMap::new();
+    let mut errors = Vec::new();
+    for (name, steps) in table {
+        let mut chars = name.chars();
+        let register = match (chars.next(), chars.next()) {
+            (Some(c), None) if c.is_ascii_alphabetic() => c.to_ascii_lowercase(),
+            _ => {
+                errors.push(format!("macros: register `{name}` must be a single letter"));
+                continue;
+            }
+        };
+        let parsed: Option<Vec<MacroStep>> = steps
+            .split(',')
+            .filter(|step| !step.trim().is_empty())
+            .map(MacroStep::parse)
+            .collect();
+        match parsed {
+            Some(parsed) => {
+                macros.insert(register, parsed);
+            }
+            None => errors.push(format!("macros: can't parse `{name} = \"{steps}\"`")),
+        }
+    }
+    (macros, errors)
+}
+
+/// The inverse of [`parse_table`].
+#[must_use]
+pub fn to_table(macros: &BTreeMap<char, Vec<MacroStep>>) -> BTreeMap<String, String> {
+    macros
+        .iter()
+        .map(|(register, steps)| {
+            let steps: Vec<String> = steps.iter().map(MacroStep::to_string).collect();
+            (register.to_string(), steps.join(", "))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_table, register_for_key, to_table, MacroStep};
+    use crate::keymap::Action;
+    use eframe::egui::Key;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn parses_steps_with_and_without_counts() {
+        let step = MacroStep::parse("  3   clone-down ").unwrap();
+        assert!(step.count == 3 && step.action.name() == "clone-down");
+        let step = MacroStep::parse("delete").unwrap();
+        assert!(step.count == 1 && step.action == Action::Delete);
+        for text in ["", "3", "no-such-action", "3 delete extra", "delete 3"] {
+            assert!(MacroStep::parse(text).is_none(), "{text}");
+        }
+    }
+
+    #[test]
+    fn steps_round_trip_through_display() {
+        for text in ["move-left", "3 clone-down", "shape-diamond", "12 rotate-cw"] {
+            let step = MacroStep::parse(text).unwrap();
+            assert_eq!(step.to_string(), text);
+            assert!(MacroStep::parse(&step.to_string()) == Some(step));
+        }
+    }
+
+    #[test]
+    fn tables_round_trip_and_report_bad_entries() {
+        let table = BTreeMap::from([
+            ("a".to_string(), "2 clone-right, move-down".to_string()),
+            ("B".to_string(), "delete,".to_string()),
+            ("cc".to_string(), "delete".to_string()),
+            ("d".to_string(), "nonsense".to_string()),
+        ]);
+        let (macros, errors) = parse_table(&table);
+        assert_eq!(errors.len(), 2);
+        assert_eq!(
+            to_table(&macros),
+            BTreeMap::from([
+                ("a".to_string(), "2 clone-right, move-down".to_string()),
+                ("b".to_string(), "delete".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn registers_are_single_letters() {
+        assert_eq!(register_for_key(Key::Q), Some('q'));
+        assert_eq!(register_for_key(Key::Num1), None);
+        assert_eq!(register_for_key(Key::Escape), None);
+    }
+}
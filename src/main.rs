@@ -1,33 +1,82 @@
 #![allow(clippy::multiple_crate_versions)]
 use eframe::egui;
-use pts::{config, drawing, interactions, persistence, state, ui};
+use pts::{config, drawing, interactions, keymap, macros, persistence, state, ui};
 
 struct PointDragger {
     state: state::AppState,
     config: config::Config,
+    config_watcher: config::ConfigWatcher,
+    keymap: keymap::Keymap,
+    /// The notice the last reload showed, so a later reload only clears its own.
+    config_notice: Option<String>,
 }
 
 impl PointDragger {
     fn new() -> Self {
-        let config = config::Config::load();
         let points = persistence::load_points();
-        Self {
+        let mut app = Self {
             state: state::AppState::new(points),
-            config,
+            config: config::Config::default(),
+            config_watcher: config::ConfigWatcher::new(config::CONFIG_FILE),
+            keymap: keymap::Keymap::default(),
+            config_notice: None,
+        };
+        app.reload_config();
+        app
+    }
+
+    /// Apply any edits to the config file, keeping the last good config on a parse error.
+    ///
+    /// Display settings toggled from the keyboard keep their session values.
+    fn reload_config(&mut self) {
+        let notice = match self.config_watcher.poll() {
+            Some(Ok(mut config)) => {
+                for &setting in &self.state.toggled_view {
+                    config.copy_setting(&self.config, setting);
+                }
+                let (keymap, mut errors) = keymap::Keymap::new(&config.keymap);
+                let (registers, macro_errors) = macros::parse_table(&config.macros);
+                errors.extend(macro_errors);
+                errors.extend(config.custom_shape_errors());
+                let undefined = self.state.undefined_custom_shapes(&config);
+                if !undefined.is_empty() {
+                    errors.push(format!("Undefined custom shapes: {}", undefined.join(", ")));
+                }
+                self.state.macros.extend(registers);
+                self.config = config;
+                self.keymap = keymap;
+                (!errors.is_empty()).then(|| errors.join("\n"))
+            }
+            Some(Err(e)) => Some(e),
+            None => return,
+        };
+        // Leave other notices, e.g. a failed load, unless there is a new problem to show
+        if notice.is_some() || self.state.notice == self.config_notice {
+            self.state.notice.clone_from(&notice);
         }
+        self.config_notice = notice;
     }
 }
 
 impl eframe::App for PointDragger {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        ui::show_menu(ctx, &mut self.state);
+        self.reload_config();
+        ctx.request_repaint_after(config::RELOAD_INTERVAL);
+
+        ui::sync_background(ctx, &mut self.state);
+        ui::show_menu(ctx, &mut self.state, &self.config);
         ui::show_tool_panel(ctx, &self.config, &mut self.state);
-        ui::show_help_window(ctx, &mut self.state);
-        ui::show_status_bar(ctx, &self.state);
-        ui::handle_keyboard(ctx, &mut self.state, &mut self.config);
+        ui::show_help_window(ctx, &mut self.state, &self.keymap);
+        ui::show_status_bar(ctx, &mut self.state, &self.config);
+        ui::show_command_palette(ctx, &mut self.state, &mut self.config, &self.keymap);
+        ui::show_prompt(ctx, &mut self.state, &self.config);
+        ui::handle_keyboard(ctx, &mut self.state, &mut self.config, &self.keymap);
 
         egui::CentralPanel::default().show(ctx, |ui| {
             let response = drawing::draw_canvas(ui, &self.state, &self.config);
+            if self.config.show_minimap {
+                ui::show_minimap(ui, &self.state, &self.config, response.rect);
+            }
 
             if ctx.input(|i| i.modifiers.ctrl) {
                 let scroll_delta = ctx.input(|i| i.smooth_scroll_delta.y);
@@ -37,10 +86,17 @@ impl eframe::App for PointDragger {
                 }
             }
 
+            self.state.cursor = response.hover_pos();
+            if interactions::ruler_guides(&mut self.state, &self.config, &response) {
+                return;
+            }
+
             if self.state.interaction_mode == state::InteractionMode::BoxSelect {
                 interactions::box_select(&mut self.state, &self.config, &response);
             } else if self.state.interaction_mode == state::InteractionMode::Paintbrush {
                 interactions::paintbrush(&mut self.state, &self.config, &response);
+            } else if self.state.interaction_mode == state::InteractionMode::Measure {
+                interactions::measure(&mut self.state, &self.config, &response);
             } else {
                 interactions::normal(&mut self.state, &self.config, &response);
             }
@@ -49,6 +105,12 @@ impl eframe::App for PointDragger {
 }
 
 fn main() -> eframe::Result<()> {
+    if std::env::args().any(|arg| arg == "--keys") {
+        let (keymap, _) = keymap::Keymap::new(&config::Config::load().keymap);
+        print!("{}", keymap.cheat_sheet());
+        return Ok(());
+    }
+
     let options = eframe::NativeOptions::default();
     eframe::run_native(
         "Pts",
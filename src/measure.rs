@@ -0,0 +1,99 @@
+//! Distances and angles for the measure tool.
+
+use eframe::egui::Pos2;
+
+/// A segment's length, and its angle in degrees anticlockwise from the x axis as drawn.
+#[must_use]
+pub fn segment(from: Pos2, to: Pos2) -> (f32, f32) {
+    let delta = to - from;
+    // Canvas y runs down the screen, so flip it to measure anticlockwise (subtracting from
+    // zero rather than negating keeps a flat segment leftwards at 180° rather than -180°)
+    let angle = (0.0 - delta.y).atan2(delta.x).to_degrees();
+    (delta.length(), angle)
+}
+
+/// A segment's length and angle, e.g. `141.4 at 45.0°`.
+#[must_use]
+pub fn describe(from: Pos2, to: Pos2) -> String {
+    let (length, angle) = segment(from, to);
+    format!("{length:.1} at {angle:.1}°")
+}
+
+/// The angle in degrees between the segments meeting at `corner`, from 0 to 180.
+#[must_use]
+pub fn corner_angle(before: Pos2, corner: Pos2, after: Pos2) -> f32 {
+    let (u, v) = (before - corner, after - corner);
+    (u.x * v.y - u.y * v.x).atan2(u.dot(v)).abs().to_degrees()
+}
+
+/// The total length of the chain of segments through `anchors`.
+#[must_use]
+pub fn chain_length(anchors: &[Pos2]) -> f32 {
+    anchors
+        .windows(2)
+        .map(|pair| pair[0].distance(pair[1]))
+        .sum()
+}
+
+/// The distance between every pair of `points`, by index, nearest first.
+#[must_use]
+pub fn pairwise(points: &[Pos2]) -> Vec<(usize, usize, f32)> {
+    let mut pairs: Vec<(usize, usize, f32)> = (0..points.len())
+        .flat_map(|i| (i + 1..points.len()).map(move |j| (i, j, points[i].distance(points[j]))))
+        .collect();
+    pairs.sort_by(|a, b| a.2.total_cmp(&b.2));
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{chain_length, corner_angle, describe, pairwise, segment};
+    use eframe::egui::pos2;
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-3, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn segment_angles_run_anticlockwise_as_drawn() {
+        let origin = pos2(0.0, 0.0);
+        let (length, angle) = segment(origin, pos2(3.0, -4.0));
+        assert_close(length, 5.0);
+        assert_close(angle, 53.130);
+        assert_close(segment(origin, pos2(-1.0, 0.0)).1, 180.0);
+        assert_close(segment(origin, pos2(0.0, 1.0)).1, -90.0);
+        assert_eq!(describe(origin, pos2(100.0, -100.0)), "141.4 at 45.0°");
+    }
+
+    #[test]
+    fn corner_angles_are_unsigned() {
+        let corner = pos2(0.0, 0.0);
+        assert_close(corner_angle(pos2(1.0, 0.0), corner, pos2(0.0, 1.0)), 90.0);
+        assert_close(corner_angle(pos2(0.0, 1.0), corner, pos2(1.0, 0.0)), 90.0);
+        assert_close(corner_angle(pos2(1.0, 0.0), corner, pos2(-1.0, 0.0)), 180.0);
+        assert_close(corner_angle(pos2(1.0, 0.0), corner, pos2(1.0, 1.0)), 45.0);
+        assert_close(corner_angle(pos2(2.0, 0.0), corner, pos2(5.0, 0.0)), 0.0);
+    }
+
+    #[test]
+    fn chain_length_sums_each_segment() {
+        assert_close(chain_length(&[]), 0.0);
+        assert_close(chain_length(&[pos2(1.0, 1.0)]), 0.0);
+        let chain = [
+            pos2(0.0, 0.0),
+            pos2(3.0, 4.0),
+            pos2(3.0, 0.0),
+            pos2(0.0, 0.0),
+        ];
+        assert_close(chain_length(&chain), 12.0);
+    }
+
+    #[test]
+    fn pairwise_distances_are_nearest_first() {
+        let points = [pos2(0.0, 0.0), pos2(10.0, 0.0), pos2(0.0, 1.0)];
+        let pairs = pairwise(&points);
+        let indices: Vec<(usize, usize)> = pairs.iter().map(|&(i, j, _)| (i, j)).collect();
+        assert_eq!(indices, [(0, 2), (0, 1), (1, 2)]);
+        assert_close(pairs[0].2, 1.0);
+    }
+}
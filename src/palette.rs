@@ -0,0 +1,102 @@
+//! Command palette state and fuzzy matching over every action.
+
+use crate::keymap::{Action, Keymap};
+
+/// An open command palette: the typed query and the highlighted entry.
+#[derive(Clone, Default)]
+pub struct Palette {
+    pub query: String,
+    pub selected: usize,
+}
+
+/// A palette row: an action with its current key binding, if any.
+pub struct Entry {
+    pub action: Action,
+    pub keys: Option<String>,
+}
+
+impl Palette {
+    /// Actions matching the query, best match first.
+    ///
+    /// Pending modes are left out since they only make sense as the first half of a chord.
+    #[must_use]
+    pub fn entries(&self, keymap: &Keymap) -> Vec<Entry> {
+        let mut scored: Vec<(i32, Entry)> = Action::all()
+            .into_iter()
+            .filter(|action| !matches!(action, Action::Pending(_)))
+            .filter_map(|action| {
+                let score = fuzzy_score(&self.query, &action.description())
+                    .max(fuzzy_score(&self.query, &action.name()))?;
+                let keys = keymap.keys_for(&action);
+                Some((score, Entry { action, keys }))
+            })
+            .collect();
+        scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        scored.into_iter().map(|(_, entry)| entry).collect()
+    }
+}
+
+/// Score `text` against `query` as a case-insensitive subsequence, or `None` if it doesn't match.
+///
+/// Consecutive characters and matches at the start of a word score higher,
+/// so `"cl l"` ranks "Clone adjacent, left" above "Toggle grid visibility".
+#[must_use]
+pub fn fuzzy_score(query: &str, text: &str) -> Option<i32> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut pos = 0;
+    let mut prev_match: Option<usize> = None;
+
+    for q in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let offset = text[pos..].iter().position(|&c| c == q)?;
+        let idx = pos + offset;
+        score += 1;
+        if prev_match.is_some_and(|prev| prev + 1 == idx) {
+            score += 5;
+        }
+        if idx == 0 || !text[idx - 1].is_alphanumeric() {
+            score += 3;
+        }
+        score -= i32::try_from(offset).unwrap_or(i32::MAX).min(10);
+        prev_match = Some(idx);
+        pos = idx + 1;
+    }
+    Some(score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fuzzy_score, Palette};
+    use crate::keymap::{Action, Keymap};
+
+    #[test]
+    fn matches_case_insensitive_subsequences() {
+        assert!(fuzzy_score("CLN", "clone").is_some());
+        assert!(fuzzy_score("", "anything").is_some());
+        assert_eq!(fuzzy_score("xyz", "clone"), None);
+        assert_eq!(fuzzy_score("nolc", "clone"), None);
+    }
+
+    #[test]
+    fn ranks_consecutive_and_word_start_matches_higher() {
+        let score = |query, text| fuzzy_score(query, text).unwrap();
+        assert!(score("clo", "clone") > score("clo", "c-l-o"));
+        assert!(score("g", "a grid") > score("g", "agrid"));
+        assert!(score("cl l", "Clone adjacent, left") > score("cl l", "Clone in place"));
+    }
+
+    #[test]
+    fn entries_put_the_best_match_first() {
+        let palette = Palette {
+            query: "toggle grid".to_string(),
+            selected: 0,
+        };
+        let entries = palette.entries(&Keymap::default());
+        assert!(entries
+            .first()
+            .is_some_and(|entry| entry.action == Action::ToggleGrid));
+        assert!(entries
+            .iter()
+            .all(|entry| !matches!(entry.action, Action::Pending(_))));
+    }
+}
@@ -1,17 +1,118 @@
 //! Point data structures and JSON serialization.
 
+use eframe::egui;
 use facet::Facet;
 use std::fs;
 
 const POINTS_FILE: &str = "points.json";
 
-#[derive(Copy, Clone, Facet, PartialEq)]
+/// Layer that points without one belong to.
+pub const DEFAULT_LAYER: &str = "Default";
+
+#[derive(Clone, Facet, PartialEq)]
 #[repr(u8)]
 pub enum PointShape {
     Circle,
     Square,
     Diamond,
     Semicircle,
+    /// Equilateral triangle, pointing up.
+    Triangle,
+    /// Regular polygon with the given number of sides.
+    Polygon(u8),
+    /// Five-pointed star.
+    Star,
+    /// Plus-shaped cross.
+    Cross,
+    /// Hollow circle.
+    Ring,
+    /// A shape declared under `custom_shapes` in the config, by name.
+    Custom(String),
+}
+
+impl PointShape {
+    pub const ALL: [PointShape; 9] = [
+        PointShape::Circle,
+        PointShape::Square,
+        PointShape::Diamond,
+        PointShape::Semicircle,
+        PointShape::Triangle,
+        PointShape::Polygon(6),
+        PointShape::Star,
+        PointShape::Cross,
+        PointShape::Ring,
+    ];
+
+    /// Lowercase identifier, as used in action names.
+    #[must_use]
+    pub fn name(&self) -> String {
+        match self {
+            PointShape::Circle => "circle".to_string(),
+            PointShape::Square => "square".to_string(),
+            PointShape::Diamond => "diamond".to_string(),
+            PointShape::Semicircle => "semicircle".to_string(),
+            PointShape::Triangle => "triangle".to_string(),
+            PointShape::Polygon(sides) => format!("polygon-{sides}"),
+            PointShape::Star => "star".to_string(),
+            PointShape::Cross => "cross".to_string(),
+            PointShape::Ring => "ring".to_string(),
+            PointShape::Custom(name) => format!("custom:{name}"),
+        }
+    }
+
+    /// Parse a name produced by [`Self::name`].
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        if let Some(custom) = name.strip_prefix("custom:") {
+            return Some(PointShape::Custom(custom.to_string()));
+        }
+        if let Some(sides) = name.strip_prefix("polygon-") {
+            return sides
+                .parse()
+                .ok()
+                .filter(|sides| *sides >= 3)
+                .map(PointShape::Polygon);
+        }
+        Self::ALL.into_iter().find(|shape| shape.name() == name)
+    }
+
+    #[must_use]
+    pub fn label(&self) -> String {
+        match self {
+            PointShape::Circle => "Circle".to_string(),
+            PointShape::Square => "Square".to_string(),
+            PointShape::Diamond => "Diamond".to_string(),
+            PointShape::Semicircle => "Semicircle".to_string(),
+            PointShape::Triangle => "Triangle".to_string(),
+            PointShape::Polygon(5) => "Pentagon".to_string(),
+            PointShape::Polygon(6) => "Hexagon".to_string(),
+            PointShape::Polygon(8) => "Octagon".to_string(),
+            PointShape::Polygon(sides) => format!("{sides}-sided polygon"),
+            PointShape::Star => "Star".to_string(),
+            PointShape::Cross => "Cross".to_string(),
+            PointShape::Ring => "Ring".to_string(),
+            PointShape::Custom(name) => name.clone(),
+        }
+    }
+}
+
+/// Per-point overrides of the config's point style; unset fields use the config.
+#[derive(Facet, Clone, Default, PartialEq)]
+pub struct PointStyle {
+    #[facet(default, skip_serializing_if = Option::is_none)]
+    pub fill: Option<bool>,
+    #[facet(default, skip_serializing_if = Option::is_none)]
+    pub stroke_width: Option<f32>,
+    #[facet(default, skip_serializing_if = Option::is_none)]
+    pub stroke_color: Option<String>,
+}
+
+impl PointStyle {
+    /// Whether nothing is overridden, so the style can be left out of `points.json`.
+    #[must_use]
+    pub fn is_unset(&self) -> bool {
+        *self == Self::default()
+    }
 }
 
 #[derive(Facet, Clone)]
@@ -22,16 +123,232 @@ pub struct Point {
     pub shape: PointShape,
     #[facet(default = 0.0)]
     pub rotation: f32, // in radians
+    #[facet(default, skip_serializing_if = PointStyle::is_unset)]
+    pub style: PointStyle,
+    /// Text drawn beside the point.
+    #[facet(default, skip_serializing_if = Option::is_none)]
+    pub label: Option<String>,
+    /// Name of the layer the point is on.
+    #[facet(default = DEFAULT_LAYER.to_string(), skip_serializing_if = is_default_layer)]
+    pub layer: String,
+    /// Id of the innermost group the point belongs to.
+    #[facet(default, skip_serializing_if = Option::is_none)]
+    pub group: Option<u64>,
+    /// Locked points are drawn but can't be clicked, dragged or box-selected.
+    #[facet(default, skip_serializing_if = is_false)]
+    pub locked: bool,
+    #[facet(default, skip_serializing_if = is_false)]
+    pub hidden: bool,
 }
 
-#[derive(Facet, Clone)]
-struct Points {
-    points: Vec<Point>,
+#[allow(clippy::ptr_arg)]
+fn is_default_layer(layer: &String) -> bool {
+    layer == DEFAULT_LAYER
+}
+
+/// A named set of points drawn together; later layers are drawn on top.
+#[derive(Facet, Clone, PartialEq)]
+pub struct Layer {
+    pub name: String,
+    #[facet(default = true)]
+    pub visible: bool,
+    /// Locked points are drawn but can't be clicked or box-selected.
+    #[facet(default)]
+    pub locked: bool,
+    /// From 0 (invisible) to 1.
+    #[facet(default = 1.0)]
+    pub opacity: f32,
 }
 
+impl Layer {
+    #[must_use]
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            visible: true,
+            locked: false,
+            opacity: 1.0,
+        }
+    }
+}
+
+/// A set of points (and nested groups) selected and transformed as one.
+///
+/// Membership is recorded on the members: points name their group, groups their parent.
+#[derive(Facet, Clone, PartialEq)]
+pub struct Group {
+    pub id: u64,
+    #[facet(default, skip_serializing_if = Option::is_none)]
+    pub parent: Option<u64>,
+}
+
+/// A guide line dragged out of a ruler, which dragged points snap to.
+#[derive(Facet, Clone, Copy, PartialEq)]
+pub struct RulerGuide {
+    /// Runs down the canvas at x = `position`, rather than across it at y = `position`.
+    #[facet(default, skip_serializing_if = is_false)]
+    pub vertical: bool,
+    pub position: f32,
+}
+
+/// A reference image drawn under the grid, e.g. to trace a logo over.
+#[derive(Facet, Clone, PartialEq)]
+pub struct Background {
+    /// A PNG or JPEG file, relative to the working directory.
+    pub path: String,
+    /// Canvas position of the image's top left corner.
+    #[facet(default)]
+    pub x: f32,
+    #[facet(default)]
+    pub y: f32,
+    /// Canvas units per image pixel.
+    #[facet(default = 1.0)]
+    pub scale: f32,
+    /// From 0 (invisible) to 1.
+    #[facet(default = 0.5)]
+    pub opacity: f32,
+    /// Locked images can't be dragged.
+    #[facet(default, skip_serializing_if = is_false)]
+    pub locked: bool,
+}
+
+impl Background {
+    #[must_use]
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            x: 0.0,
+            y: 0.0,
+            scale: 1.0,
+            opacity: 0.5,
+            locked: false,
+        }
+    }
+}
+
+/// A connection between two points, by id.
+#[derive(Facet, Clone, PartialEq)]
+pub struct Edge {
+    pub from: u64,
+    pub to: u64,
+    /// Drawn with an arrowhead at `to`.
+    #[facet(default, skip_serializing_if = is_false)]
+    pub directed: bool,
+}
+
+impl Edge {
+    /// Whether this edge joins the same two points as `other`, ignoring direction
+    /// unless both are directed the same way.
+    #[must_use]
+    pub fn same_as(&self, other: &Edge) -> bool {
+        let forwards = self.from == other.from && self.to == other.to;
+        let backwards = self.from == other.to && self.to == other.from;
+        forwards || (backwards && !(self.directed && other.directed))
+    }
+
+    #[must_use]
+    pub fn touches(&self, id: u64) -> bool {
+        self.from == id || self.to == id
+    }
+}
+
+/// A named route through points, by id: an open polyline, or a polygon when closed.
+#[derive(Facet, Clone, PartialEq)]
+pub struct Path {
+    pub name: String,
+    pub points: Vec<u64>,
+    #[facet(default, skip_serializing_if = is_false)]
+    pub closed: bool,
+}
+
+#[allow(clippy::trivially_copy_pass_by_ref)]
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+/// Everything saved to `points.json`.
+#[derive(Facet, Clone, Default)]
+pub struct Document {
+    pub points: Vec<Point>,
+    #[facet(default)]
+    pub edges: Vec<Edge>,
+    #[facet(default)]
+    pub paths: Vec<Path>,
+    /// Bottom to top. Layers named by points but missing here are added when loading.
+    #[facet(default)]
+    pub layers: Vec<Layer>,
+    #[facet(default)]
+    pub groups: Vec<Group>,
+    #[facet(default)]
+    pub guides: Vec<RulerGuide>,
+    #[facet(default, skip_serializing_if = Option::is_none)]
+    pub background: Option<Background>,
+}
+
+impl Document {
+    /// The sample points shown on first launch and after a reset.
+    #[must_use]
+    pub fn sample() -> Self {
+        Self {
+            points: default_points(),
+            edges: Vec::new(),
+            paths: Vec::new(),
+            layers: vec![Layer::new(DEFAULT_LAYER)],
+            groups: Vec::new(),
+            guides: Vec::new(),
+            background: None,
+        }
+    }
+}
+
+/// The layer a point is on, if it is in `layers`.
+#[must_use]
+pub fn layer_of<'a>(layers: &'a [Layer], pt: &Point) -> Option<&'a Layer> {
+    layers.iter().find(|layer| layer.name == pt.layer)
+}
+
+/// Read `points.json`, or the sample points if it doesn't exist yet.
+///
+/// Custom shapes are stored by name, so a file using shapes missing from the
+/// config still loads; see `AppState::undefined_custom_shapes`.
+///
+/// # Errors
+///
+/// Returns a message describing the problem if the file can't be read or parsed.
+pub fn load_document() -> Result<Document, String> {
+    match fs::read_to_string(POINTS_FILE) {
+        Ok(json) => {
+            facet_json::from_str::<Document>(&json).map_err(|e| format!("{POINTS_FILE}: {e}"))
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Document::sample()),
+        Err(e) => Err(format!("{POINTS_FILE}: {e}")),
+    }
+}
+
+/// Decode a PNG or JPEG image file.
+///
+/// # Errors
+///
+/// Returns a message describing the problem if the file can't be read or decoded.
+pub fn load_image(path: &str) -> Result<egui::ColorImage, String> {
+    let image = image::open(path).map_err(|e| format!("{path}: {e}"))?;
+    let rgba = image.to_rgba8();
+    let size = [rgba.width(), rgba.height()].map(|side| side as usize);
+    Ok(egui::ColorImage::from_rgba_unmultiplied(
+        size,
+        rgba.as_flat_samples().as_slice(),
+    ))
+}
+
+/// The sample points to start each launch from, deleting any `points.json` left by the last session.
 #[must_use]
 pub fn load_points() -> Vec<Point> {
     let _ = fs::remove_file(POINTS_FILE);
+    default_points()
+}
+
+#[must_use]
+pub fn default_points() -> Vec<Point> {
     vec![
         Point {
             id: 1,
@@ -39,6 +356,12 @@ pub fn load_points() -> Vec<Point> {
             y: 200.0,
             shape: PointShape::Circle,
             rotation: 0.0,
+            style: PointStyle::default(),
+            label: None,
+            layer: DEFAULT_LAYER.to_string(),
+            group: None,
+            locked: false,
+            hidden: false,
         },
         Point {
             id: 2,
@@ -46,6 +369,12 @@ pub fn load_points() -> Vec<Point> {
             y: 300.0,
             shape: PointShape::Square,
             rotation: 0.0,
+            style: PointStyle::default(),
+            label: None,
+            layer: DEFAULT_LAYER.to_string(),
+            group: None,
+            locked: false,
+            hidden: false,
         },
         Point {
             id: 3,
@@ -53,6 +382,12 @@ pub fn load_points() -> Vec<Point> {
             y: 400.0,
             shape: PointShape::Diamond,
             rotation: 0.0,
+            style: PointStyle::default(),
+            label: None,
+            layer: DEFAULT_LAYER.to_string(),
+            group: None,
+            locked: false,
+            hidden: false,
         },
         Point {
             id: 4,
@@ -60,14 +395,17 @@ pub fn load_points() -> Vec<Point> {
             y: 500.0,
             shape: PointShape::Semicircle,
             rotation: 0.0,
+            style: PointStyle::default(),
+            label: None,
+            layer: DEFAULT_LAYER.to_string(),
+            group: None,
+            locked: false,
+            hidden: false,
         },
     ]
 }
 
-pub fn save_points(points: &[Point]) {
-    let wrapped = Points {
-        points: points.to_vec(),
-    };
-    let json = facet_json::to_string(&wrapped);
+pub fn save_document(document: &Document) {
+    let json = facet_json::to_string(document);
     let _ = fs::write(POINTS_FILE, json);
 }
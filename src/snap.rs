@@ -0,0 +1,275 @@
+//! Snapping a dragged selection to other points or ruler guides, and the guides showing what it
+//! lined up with.
+
+use eframe::egui::{self, Pos2, Rangef, Rect};
+
+/// Shifts smaller than this count as already lined up.
+const ALIGNED: f32 = 0.01;
+
+/// Length of the ticks at each end of a gap guide.
+pub const GAP_TICK: f32 = 4.0;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum GuideKind {
+    /// Edges or centres lined up along this line.
+    Align,
+    /// One of two or more equal gaps between neighbouring points.
+    Gap,
+}
+
+/// A line drawn over the canvas while dragging, in canvas coordinates.
+#[derive(Clone, Copy)]
+pub struct Guide {
+    pub kind: GuideKind,
+    pub from: Pos2,
+    pub to: Pos2,
+}
+
+/// How far to move the selection, and the guides to draw once it has moved.
+#[derive(Default)]
+pub struct Snap {
+    pub offset: egui::Vec2,
+    pub guides: Vec<Guide>,
+}
+
+/// A shift along one axis that would line the selection up with something.
+struct Candidate {
+    shift: f32,
+    guides: Vec<Guide>,
+}
+
+fn range(rect: Rect, axis: usize) -> Rangef {
+    if axis == 0 {
+        rect.x_range()
+    } else {
+        rect.y_range()
+    }
+}
+
+/// A position from its coordinate along `axis` and across it.
+fn on_axis(axis: usize, along: f32, across: f32) -> Pos2 {
+    if axis == 0 {
+        egui::pos2(along, across)
+    } else {
+        egui::pos2(across, along)
+    }
+}
+
+fn gap_guide(axis: usize, from: f32, to: f32, across: f32) -> Guide {
+    Guide {
+        kind: GuideKind::Gap,
+        from: on_axis(axis, from, across),
+        to: on_axis(axis, to, across),
+    }
+}
+
+/// Snap `moving`, the selection's bounds, to the edges, centres and spacing of `others`.
+///
+/// Each axis snaps separately, to the nearest candidate within `distance`.
+#[must_use]
+pub fn to_rects(moving: Rect, others: &[Rect], distance: f32) -> Snap {
+    let mut snap = Snap::default();
+    for axis in 0..2 {
+        let nearest = candidates(moving, others, axis)
+            .into_iter()
+            .filter(|candidate| candidate.shift.abs() <= distance)
+            .min_by(|a, b| a.shift.abs().total_cmp(&b.shift.abs()));
+        if let Some(candidate) = nearest {
+            snap.offset[axis] = candidate.shift;
+        }
+    }
+    // Every guide that holds once moved, not just the one snapped to
+    let moved = moving.translate(snap.offset);
+    for axis in 0..2 {
+        for candidate in candidates(moved, others, axis) {
+            if candidate.shift.abs() < ALIGNED {
+                snap.guides.extend(candidate.guides);
+            }
+        }
+    }
+    snap
+}
+
+/// The shift putting an edge or the centre of `moving` on the nearest of the vertical lines at
+/// `xs`, and likewise for the horizontal lines at `ys`, on each axis within `distance`.
+#[must_use]
+pub fn to_lines(moving: Rect, xs: &[f32], ys: &[f32], distance: f32) -> egui::Vec2 {
+    let mut offset = egui::Vec2::ZERO;
+    for (axis, lines) in [xs, ys].into_iter().enumerate() {
+        let along = range(moving, axis);
+        offset[axis] = lines
+            .iter()
+            .flat_map(|&line| [along.min, along.center(), along.max].map(|feature| line - feature))
+            .filter(|shift| shift.abs() <= distance)
+            .min_by(|a, b| a.abs().total_cmp(&b.abs()))
+            .unwrap_or(0.0);
+    }
+    offset
+}
+
+fn candidates(moving: Rect, others: &[Rect], axis: usize) -> Vec<Candidate> {
+    let mut found = alignments(moving, others, axis);
+    found.extend(gaps(moving, others, axis));
+    found
+}
+
+/// Shifts lining up the selection's edges or centre with another point's.
+fn alignments(moving: Rect, others: &[Rect], axis: usize) -> Vec<Candidate> {
+    let along = range(moving, axis);
+    let across = range(moving, 1 - axis);
+    let mut found = Vec::new();
+    for other in others {
+        let other_along = range(*other, axis);
+        let other_across = range(*other, 1 - axis);
+        let start = across.min.min(other_across.min);
+        let end = across.max.max(other_across.max);
+        for target in [other_along.min, other_along.center(), other_along.max] {
+            for feature in [along.min, along.center(), along.max] {
+                found.push(Candidate {
+                    shift: target - feature,
+                    guides: vec![Guide {
+                        kind: GuideKind::Align,
+                        from: on_axis(axis, target, start),
+                        to: on_axis(axis, target, end),
+                    }],
+                });
+            }
+        }
+    }
+    found
+}
+
+/// Shifts making the gap to a neighbour in the same row or column match the next gap along,
+/// or centring the selection between its two neighbours.
+fn gaps(moving: Rect, others: &[Rect], axis: usize) -> Vec<Candidate> {
+    let along = range(moving, axis);
+    let across = range(moving, 1 - axis);
+    let row: Vec<Rangef> = others
+        .iter()
+        .filter(|other| range(**other, 1 - axis).intersects(across))
+        .map(|other| range(*other, axis))
+        .collect();
+    // Nearest first on each side
+    let mut before: Vec<Rangef> = row
+        .iter()
+        .copied()
+        .filter(|other| other.center() < along.center())
+        .collect();
+    before.sort_by(|a, b| b.max.total_cmp(&a.max));
+    let mut after: Vec<Rangef> = row
+        .iter()
+        .copied()
+        .filter(|other| other.center() > along.center())
+        .collect();
+    after.sort_by(|a, b| a.min.total_cmp(&b.min));
+
+    let mid = across.center();
+    let mut found = Vec::new();
+    if let [near, rest @ ..] = before.as_slice() {
+        if let Some(far) = rest.iter().find(|far| far.max <= near.min) {
+            let gap = near.min - far.max;
+            found.push(Candidate {
+                shift: near.max + gap - along.min,
+                guides: vec![
+                    gap_guide(axis, far.max, near.min, mid),
+                    gap_guide(axis, near.max, along.min, mid),
+                ],
+            });
+        }
+    }
+    if let [near, rest @ ..] = after.as_slice() {
+        if let Some(far) = rest.iter().find(|far| far.min >= near.max) {
+            let gap = far.min - near.max;
+            found.push(Candidate {
+                shift: near.min - gap - along.max,
+                guides: vec![
+                    gap_guide(axis, along.max, near.min, mid),
+                    gap_guide(axis, near.max, far.min, mid),
+                ],
+            });
+        }
+    }
+    if let (Some(left), Some(right)) = (before.first(), after.first()) {
+        if left.max <= along.min && along.max <= right.min {
+            found.push(Candidate {
+                shift: (left.max + right.min - along.min - along.max) / 2.0,
+                guides: vec![
+                    gap_guide(axis, left.max, along.min, mid),
+                    gap_guide(axis, along.max, right.min, mid),
+                ],
+            });
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{to_rects, GuideKind, Snap};
+    use eframe::egui::{pos2, vec2, Pos2, Rect};
+
+    fn square(min: Pos2) -> Rect {
+        Rect::from_min_size(min, vec2(20.0, 20.0))
+    }
+
+    fn guides(snap: &Snap, kind: GuideKind) -> Vec<(Pos2, Pos2)> {
+        snap.guides
+            .iter()
+            .filter(|guide| guide.kind == kind)
+            .map(|guide| (guide.from, guide.to))
+            .collect()
+    }
+
+    #[test]
+    fn snaps_within_the_distance_and_not_beyond() {
+        let others = [square(pos2(90.0, 90.0))];
+        let moving = square(pos2(93.0, 300.0));
+        assert_eq!(to_rects(moving, &others, 5.0).offset, vec2(-3.0, 0.0));
+        assert_eq!(to_rects(moving, &others, 3.0).offset, vec2(-3.0, 0.0));
+        let missed = to_rects(moving, &others, 2.9);
+        assert_eq!(missed.offset, vec2(0.0, 0.0));
+        assert!(missed.guides.is_empty());
+    }
+
+    #[test]
+    fn aligned_edges_and_centres_each_get_a_guide() {
+        let others = [square(pos2(90.0, 90.0))];
+        let snap = to_rects(square(pos2(93.0, 300.0)), &others, 5.0);
+        // Spanning both points, from the top of the other to the bottom of the moved one
+        assert_eq!(
+            guides(&snap, GuideKind::Align),
+            [90.0, 100.0, 110.0].map(|x| (pos2(x, 90.0), pos2(x, 320.0)))
+        );
+        assert!(guides(&snap, GuideKind::Gap).is_empty());
+    }
+
+    #[test]
+    fn snaps_to_repeat_the_gap_between_neighbours() {
+        let others = [square(pos2(0.0, 0.0)), square(pos2(40.0, 0.0))];
+        let snap = to_rects(square(pos2(81.0, 0.0)), &others, 5.0);
+        assert_eq!(snap.offset, vec2(-1.0, 0.0));
+        assert_eq!(
+            guides(&snap, GuideKind::Gap),
+            [
+                (pos2(20.0, 10.0), pos2(40.0, 10.0)),
+                (pos2(60.0, 10.0), pos2(80.0, 10.0)),
+            ]
+        );
+        // The tops, centres and bottoms also line up with both others
+        assert_eq!(guides(&snap, GuideKind::Align).len(), 6);
+    }
+
+    #[test]
+    fn snaps_to_centre_between_neighbours() {
+        let others = [square(pos2(0.0, 0.0)), square(pos2(100.0, 0.0))];
+        let snap = to_rects(square(pos2(48.0, 0.0)), &others, 5.0);
+        assert_eq!(snap.offset, vec2(2.0, 0.0));
+        assert_eq!(
+            guides(&snap, GuideKind::Gap),
+            [
+                (pos2(20.0, 10.0), pos2(50.0, 10.0)),
+                (pos2(70.0, 10.0), pos2(100.0, 10.0)),
+            ]
+        );
+    }
+}
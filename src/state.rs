@@ -1,8 +1,19 @@
 //! Core application state management including points, selection, and interaction modes.
 
-use crate::persistence::{Point, PointShape};
+use crate::config::{Config, GridType, SnapMode, ViewSetting};
+use crate::geometry;
+use crate::grid;
+use crate::macros::MacroStep;
+use crate::measure;
+use crate::palette::Palette;
+use crate::persistence::{
+    self, Background, Document, Edge, Group, Layer, Path, Point, PointShape, PointStyle,
+    RulerGuide, DEFAULT_LAYER,
+};
+use crate::snap;
 use eframe::egui;
 use facet::Facet;
+use std::collections::{BTreeMap, HashMap};
 
 #[derive(Clone, Facet)]
 #[repr(u8)]
@@ -12,12 +23,46 @@ pub enum Selection {
     Multiple(Vec<usize>),
 }
 
+impl Selection {
+    #[must_use]
+    pub fn from_indices(indices: Vec<usize>) -> Self {
+        match indices.as_slice() {
+            [] => Selection::None,
+            [only] => Selection::Single(*only),
+            _ => Selection::Multiple(indices),
+        }
+    }
+}
+
 #[derive(Clone, Copy, PartialEq)]
 pub enum PendingMode {
     None,
     Clone,
     Shape,
     View,
+    Path,
+    Layer,
+    /// Waiting for the register to record a macro into.
+    Record,
+    /// Waiting for the register to play a macro from.
+    Play,
+}
+
+/// How far apart two clicks can be and still count as clicking the same spot again.
+const CLICK_SLOP: f32 = 3.0;
+
+/// Largest count prefix accepted, to keep typos like `99999→` manageable.
+pub const MAX_COUNT: u32 = 999;
+
+/// A one-line text prompt shown over the canvas, taking keyboard input while open.
+#[derive(Clone)]
+pub enum Prompt {
+    /// Editing the label of the point with this id.
+    EditLabel { id: u64, text: String },
+    /// Selecting points whose labels contain the query.
+    FindLabel { query: String },
+    /// Choosing the background image file.
+    BackgroundPath { path: String },
 }
 
 #[derive(Clone, Copy, PartialEq)]
@@ -25,21 +70,69 @@ pub enum InteractionMode {
     Normal,
     BoxSelect,
     Paintbrush,
+    /// Clicks lay down a chain of segments, each labelled with its length and angle.
+    Measure,
 }
 
+#[allow(clippy::struct_excessive_bools)]
 pub struct AppState {
     pub points: Vec<Point>,
+    /// Connections between points, by id.
+    pub edges: Vec<Edge>,
+    pub paths: Vec<Path>,
+    /// Index into `paths` of the path that path commands act on.
+    pub current_path: Option<usize>,
+    /// Bottom to top; never empty.
+    pub layers: Vec<Layer>,
+    /// Index into `layers` of the layer new points go on.
+    pub current_layer: usize,
+    /// Every group, nested or not; points refer to these by id.
+    pub groups: Vec<Group>,
     pub selection: Selection,
     pub dragging: Option<usize>,
     pub pending_mode: PendingMode,
+    /// Count typed before a command, e.g. the `12` in `12→`.
+    pub pending_count: Option<u32>,
     pub interaction_mode: InteractionMode,
     pub show_help: bool,
+    /// Macro registers, loaded from config and filled by recording.
+    pub macros: BTreeMap<char, Vec<MacroStep>>,
+    /// The register being recorded into and the steps captured so far.
+    pub recording: Option<(char, Vec<MacroStep>)>,
+    /// The command palette overlay, while it is open.
+    pub palette: Option<Palette>,
+    pub prompt: Option<Prompt>,
     pub next_id: u64,
     pub box_select_start: Option<egui::Pos2>,
     pub box_select_end: Option<egui::Pos2>,
     pub snap_to_grid: bool,
+    /// Snap dragged points to the edges, centres and spacing of the others.
+    pub snap_to_points: bool,
+    /// Alignment and spacing guides for the drag in progress.
+    pub guides: Vec<snap::Guide>,
+    /// Guide lines dragged out of the rulers.
+    pub ruler_guides: Vec<RulerGuide>,
+    /// Index into `ruler_guides` of the guide being dragged.
+    pub dragging_guide: Option<usize>,
+    /// The pointer's canvas position while it is over the canvas.
+    pub cursor: Option<egui::Pos2>,
+    /// Ends of the measured segments, in the order clicked.
+    pub measure: Vec<egui::Pos2>,
+    /// Reference image to trace over.
+    pub background: Option<Background>,
+    /// The background image, once loaded onto the GPU.
+    pub background_texture: Option<egui::TextureHandle>,
+    /// The background file last loaded, or tried, so a missing file isn't retried every frame.
+    pub loaded_background: Option<String>,
+    pub dragging_background: bool,
     pub zoom: f32,
     pub last_paint_pos: Option<egui::Pos2>,
+    /// Where the canvas was last clicked, to cycle through stacked points on repeat clicks.
+    pub last_click: Option<egui::Pos2>,
+    /// Message shown in the status bar until dismissed, e.g. a config parse error.
+    pub notice: Option<String>,
+    /// Display settings toggled this session, kept when the config file is reloaded.
+    pub toggled_view: Vec<ViewSetting>,
 }
 
 impl AppState {
@@ -51,29 +144,848 @@ impl AppState {
         } else {
             Selection::Single(0)
         };
-        Self {
+        let mut state = Self {
             points,
+            edges: Vec::new(),
+            paths: Vec::new(),
+            current_path: None,
+            layers: Vec::new(),
+            current_layer: 0,
+            groups: Vec::new(),
             selection,
             dragging: None,
             pending_mode: PendingMode::None,
+            pending_count: None,
             interaction_mode: InteractionMode::Normal,
             show_help: false,
+            macros: BTreeMap::new(),
+            recording: None,
+            palette: None,
+            prompt: None,
             next_id,
             box_select_start: None,
             box_select_end: None,
             snap_to_grid: false,
+            snap_to_points: false,
+            guides: Vec::new(),
+            ruler_guides: Vec::new(),
+            dragging_guide: None,
+            cursor: None,
+            measure: Vec::new(),
+            background: None,
+            background_texture: None,
+            loaded_background: None,
+            dragging_background: false,
             zoom: 1.0,
             last_paint_pos: None,
+            last_click: None,
+            notice: None,
+            toggled_view: Vec::new(),
+        };
+        state.ensure_layers();
+        state
+    }
+
+    /// Swap in a new document, e.g. after loading, resetting selection and drag state.
+    pub fn replace_document(&mut self, document: Document) {
+        self.next_id = document.points.iter().map(|p| p.id).max().unwrap_or(0) + 1;
+        // Drop edges left dangling by hand-edited files
+        let ids: Vec<u64> = document.points.iter().map(|pt| pt.id).collect();
+        self.edges = document
+            .edges
+            .into_iter()
+            .filter(|edge| ids.contains(&edge.from) && ids.contains(&edge.to))
+            .collect();
+        // Cleared first, as it indexes the old paths
+        self.current_path = None;
+        self.paths = document.paths;
+        self.prune_paths(&ids);
+        self.points = document.points;
+        self.layers = document.layers;
+        self.current_layer = 0;
+        self.ensure_layers();
+        self.groups = document.groups;
+        self.repair_groups();
+        self.ruler_guides = document.guides;
+        self.dragging_guide = None;
+        self.background = document.background;
+        self.dragging_background = false;
+        self.selection = Selection::None;
+        self.dragging = None;
+    }
+
+    /// Clear references to missing groups and break parent cycles left by hand-edited files,
+    /// then drop groups with no members.
+    fn repair_groups(&mut self) {
+        let ids: Vec<u64> = self.groups.iter().map(|group| group.id).collect();
+        for pt in &mut self.points {
+            pt.group = pt.group.filter(|id| ids.contains(id));
+        }
+        for idx in 0..self.groups.len() {
+            self.groups[idx].parent = self.groups[idx].parent.filter(|id| ids.contains(id));
+            if self.ancestors(self.groups[idx].id).len() > self.groups.len() {
+                self.groups[idx].parent = None;
+            }
+        }
+        self.prune_groups();
+    }
+
+    /// Drop groups with no points or subgroups, repeatedly, so emptied parents go too.
+    fn prune_groups(&mut self) {
+        loop {
+            let before = self.groups.len();
+            let used: Vec<u64> = self
+                .points
+                .iter()
+                .filter_map(|pt| pt.group)
+                .chain(self.groups.iter().filter_map(|group| group.parent))
+                .collect();
+            self.groups.retain(|group| used.contains(&group.id));
+            if self.groups.len() == before {
+                break;
+            }
+        }
+    }
+
+    /// The group and its parents, innermost first, stopping early on a cycle.
+    fn ancestors(&self, id: u64) -> Vec<u64> {
+        let mut chain = vec![id];
+        let mut current = id;
+        while let Some(parent) = self
+            .groups
+            .iter()
+            .find(|group| group.id == current)
+            .and_then(|group| group.parent)
+        {
+            if chain.len() > self.groups.len() {
+                break;
+            }
+            chain.push(parent);
+            current = parent;
         }
+        chain
+    }
+
+    /// The outermost group the point at `idx` belongs to, if any.
+    fn root_group(&self, idx: usize) -> Option<u64> {
+        self.points[idx]
+            .group
+            .and_then(|id| self.ancestors(id).last().copied())
     }
 
+    fn new_group_id(&self) -> u64 {
+        self.groups.iter().map(|group| group.id).max().unwrap_or(0) + 1
+    }
+
+    /// The point at `idx` with the rest of its outermost group, skipping points that can't be selected.
     #[must_use]
-    pub fn point_at_pos(&self, pos: egui::Pos2, radius: f32) -> Option<usize> {
-        self.points.iter().position(|pt| {
-            let dx = pos.x - pt.x;
-            let dy = pos.y - pt.y;
-            (dx * dx + dy * dy).sqrt() < radius * 2.0
-        })
+    pub fn group_members(&self, idx: usize) -> Vec<usize> {
+        let Some(root) = self.root_group(idx) else {
+            return vec![idx];
+        };
+        (0..self.points.len())
+            .filter(|&other| {
+                other == idx
+                    || (self.root_group(other) == Some(root) && self.is_point_interactive(other))
+            })
+            .collect()
+    }
+
+    /// Select the point at `idx`, or its whole group if it is in one.
+    pub fn select_point(&mut self, idx: usize) {
+        self.selection = Selection::from_indices(self.group_members(idx));
+    }
+
+    /// Put the selected points and groups into a new group. Returns false if fewer than two are selected.
+    pub fn group_selected(&mut self) -> bool {
+        let indices = self.selected_indices();
+        let mut roots: Vec<u64> = indices
+            .iter()
+            .filter_map(|&idx| self.root_group(idx))
+            .collect();
+        roots.sort_unstable();
+        roots.dedup();
+        let ungrouped = indices
+            .iter()
+            .filter(|&&idx| self.points[idx].group.is_none());
+        if roots.len() + ungrouped.count() < 2 {
+            return false;
+        }
+        let id = self.new_group_id();
+        for group in &mut self.groups {
+            if roots.contains(&group.id) {
+                group.parent = Some(id);
+            }
+        }
+        for idx in indices {
+            let pt = &mut self.points[idx];
+            if pt.group.is_none() {
+                pt.group = Some(id);
+            }
+        }
+        self.groups.push(Group { id, parent: None });
+        true
+    }
+
+    /// Dissolve the outermost group of each selected point, one level at a time. Returns false if none are grouped.
+    pub fn ungroup_selected(&mut self) -> bool {
+        let roots: Vec<u64> = self
+            .selected_indices()
+            .into_iter()
+            .filter_map(|idx| self.root_group(idx))
+            .collect();
+        if roots.is_empty() {
+            return false;
+        }
+        for pt in &mut self.points {
+            pt.group = pt.group.filter(|id| !roots.contains(id));
+        }
+        for group in &mut self.groups {
+            group.parent = group.parent.filter(|id| !roots.contains(id));
+        }
+        self.groups.retain(|group| !roots.contains(&group.id));
+        true
+    }
+
+    /// Copy a group and its parents for cloned points, reusing copies already made.
+    fn copy_group(&mut self, id: u64, copies: &mut HashMap<u64, u64>) -> u64 {
+        if let Some(&copy) = copies.get(&id) {
+            return copy;
+        }
+        let parent = self
+            .groups
+            .iter()
+            .find(|group| group.id == id)
+            .and_then(|group| group.parent)
+            .map(|parent| self.copy_group(parent, copies));
+        let copy = self.new_group_id();
+        self.groups.push(Group { id: copy, parent });
+        copies.insert(id, copy);
+        copy
+    }
+
+    /// Add any layer named by a point but not listed, so every point has one.
+    fn ensure_layers(&mut self) {
+        if self.layers.is_empty() {
+            self.layers.push(Layer::new(DEFAULT_LAYER));
+        }
+        for pt in &self.points {
+            if !self.layers.iter().any(|layer| layer.name == pt.layer) {
+                self.layers.push(Layer::new(&pt.layer));
+            }
+        }
+    }
+
+    fn layer_index(&self, name: &str) -> Option<usize> {
+        self.layers.iter().position(|layer| layer.name == name)
+    }
+
+    /// Whether the point is drawn: not hidden itself, nor on a hidden layer.
+    #[must_use]
+    pub fn is_visible(&self, pt: &Point) -> bool {
+        !pt.hidden && persistence::layer_of(&self.layers, pt).is_none_or(|layer| layer.visible)
+    }
+
+    /// Opacity of the point's layer, multiplied into every colour it is drawn with.
+    #[must_use]
+    pub fn opacity(&self, pt: &Point) -> f32 {
+        persistence::layer_of(&self.layers, pt).map_or(1.0, |layer| layer.opacity.clamp(0.0, 1.0))
+    }
+
+    #[must_use]
+    pub fn is_point_visible(&self, idx: usize) -> bool {
+        self.is_visible(&self.points[idx])
+    }
+
+    /// Whether the point can be clicked or box-selected: visible, and neither it nor its layer locked.
+    #[must_use]
+    pub fn is_point_interactive(&self, idx: usize) -> bool {
+        let pt = &self.points[idx];
+        self.is_visible(pt)
+            && !pt.locked
+            && persistence::layer_of(&self.layers, pt).is_none_or(|layer| !layer.locked)
+    }
+
+    /// Hide the selected points, deselecting them. Returns how many were hidden.
+    pub fn hide_selected(&mut self) -> usize {
+        let indices = self.selected_indices();
+        for &idx in &indices {
+            self.points[idx].hidden = true;
+        }
+        self.selection = Selection::None;
+        indices.len()
+    }
+
+    /// Show every hidden point again, selecting them. Returns how many there were.
+    pub fn unhide_all(&mut self) -> usize {
+        let hidden: Vec<usize> = (0..self.points.len())
+            .filter(|&idx| self.points[idx].hidden)
+            .collect();
+        for &idx in &hidden {
+            self.points[idx].hidden = false;
+        }
+        self.selection = Selection::from_indices(
+            hidden
+                .iter()
+                .copied()
+                .filter(|&idx| self.is_point_interactive(idx))
+                .collect(),
+        );
+        hidden.len()
+    }
+
+    /// Lock the selected points, deselecting them. Returns how many were locked.
+    pub fn lock_selected(&mut self) -> usize {
+        let indices = self.selected_indices();
+        for &idx in &indices {
+            self.points[idx].locked = true;
+        }
+        self.selection = Selection::None;
+        indices.len()
+    }
+
+    /// Unlock every locked point, selecting them. Returns how many there were.
+    pub fn unlock_all(&mut self) -> usize {
+        let locked: Vec<usize> = (0..self.points.len())
+            .filter(|&idx| self.points[idx].locked)
+            .collect();
+        for &idx in &locked {
+            self.points[idx].locked = false;
+        }
+        self.selection = Selection::from_indices(
+            locked
+                .iter()
+                .copied()
+                .filter(|&idx| self.is_point_interactive(idx))
+                .collect(),
+        );
+        locked.len()
+    }
+
+    /// Indices of visible points, bottom layer first, keeping document order within a layer.
+    #[must_use]
+    pub fn draw_order(&self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.points.len())
+            .filter(|&idx| self.is_point_visible(idx))
+            .collect();
+        order.sort_by_key(|&idx| self.layer_index(&self.points[idx].layer));
+        order
+    }
+
+    /// Add an empty layer above the others and make it current.
+    pub fn add_layer(&mut self) {
+        let name = (1..=self.layers.len() + 1)
+            .map(|n| format!("Layer {n}"))
+            .find(|name| self.layer_index(name).is_none())
+            .unwrap_or_else(|| format!("Layer {}", self.layers.len() + 1));
+        self.layers.push(Layer::new(&name));
+        self.current_layer = self.layers.len() - 1;
+    }
+
+    /// Step the current layer up (positive) or down the stack, wrapping around.
+    pub fn cycle_current_layer(&mut self, delta: isize) {
+        let len = self.layers.len().cast_signed();
+        self.current_layer = (self.current_layer.cast_signed() + delta)
+            .rem_euclid(len)
+            .cast_unsigned();
+    }
+
+    /// Move the current layer up (positive) or down the stack, keeping it current.
+    pub fn move_current_layer(&mut self, delta: isize) {
+        let target = self.current_layer.cast_signed() + delta;
+        if target < 0 || target >= self.layers.len().cast_signed() {
+            return;
+        }
+        let target = target.cast_unsigned();
+        self.layers.swap(self.current_layer, target);
+        self.current_layer = target;
+    }
+
+    /// Delete the current layer, moving its points onto the layer below (or above, for the bottom one).
+    /// Returns false if it is the only layer.
+    pub fn delete_current_layer(&mut self) -> bool {
+        if self.layers.len() < 2 {
+            return false;
+        }
+        let removed = self.layers.remove(self.current_layer);
+        self.current_layer = self.current_layer.saturating_sub(1);
+        let target = self.layers[self.current_layer].name.clone();
+        for pt in &mut self.points {
+            if pt.layer == removed.name {
+                pt.layer.clone_from(&target);
+            }
+        }
+        true
+    }
+
+    /// Rename the current layer and the points on it. Returns false for an empty or taken name.
+    pub fn rename_current_layer(&mut self, name: &str) -> bool {
+        let name = name.trim();
+        if name.is_empty() || self.layer_index(name).is_some() {
+            return false;
+        }
+        let old = std::mem::replace(&mut self.layers[self.current_layer].name, name.to_string());
+        for pt in &mut self.points {
+            if pt.layer == old {
+                pt.layer = name.to_string();
+            }
+        }
+        true
+    }
+
+    pub fn toggle_current_layer_visible(&mut self) {
+        let layer = &mut self.layers[self.current_layer];
+        layer.visible = !layer.visible;
+    }
+
+    pub fn toggle_current_layer_locked(&mut self) {
+        let layer = &mut self.layers[self.current_layer];
+        layer.locked = !layer.locked;
+    }
+
+    /// Put every selected point on the current layer.
+    pub fn move_selected_to_layer(&mut self) {
+        let name = self.layers[self.current_layer].name.clone();
+        for idx in self.selected_indices() {
+            self.points[idx].layer.clone_from(&name);
+        }
+    }
+
+    /// Drop points that can no longer be interacted with, e.g. after hiding or locking a layer.
+    pub fn deselect_non_interactive(&mut self) {
+        let indices: Vec<usize> = self
+            .selected_indices()
+            .into_iter()
+            .filter(|&idx| self.is_point_interactive(idx))
+            .collect();
+        self.selection = Selection::from_indices(indices);
+    }
+
+    /// A copy of the points and edges, for saving or export.
+    #[must_use]
+    pub fn document(&self) -> Document {
+        Document {
+            points: self.points.clone(),
+            edges: self.edges.clone(),
+            paths: self.paths.clone(),
+            layers: self.layers.clone(),
+            groups: self.groups.clone(),
+            guides: self.ruler_guides.clone(),
+            background: self.background.clone(),
+        }
+    }
+
+    /// Drop points missing from `ids` out of every path, and paths left with fewer than two.
+    fn prune_paths(&mut self, ids: &[u64]) {
+        let current = self.current_path.map(|idx| self.paths[idx].name.clone());
+        for path in &mut self.paths {
+            path.points.retain(|id| ids.contains(id));
+        }
+        self.paths.retain(|path| path.points.len() >= 2);
+        self.current_path =
+            current.and_then(|name| self.paths.iter().position(|path| path.name == name));
+    }
+
+    fn selected_ids(&self) -> Vec<u64> {
+        self.selected_indices()
+            .into_iter()
+            .map(|idx| self.points[idx].id)
+            .collect()
+    }
+
+    /// Make a path through the selected points in selection order and make it current.
+    ///
+    /// Returns `false` if too few points are selected: two for a polyline, three for a polygon.
+    pub fn create_path(&mut self, closed: bool) -> bool {
+        let ids = self.selected_ids();
+        if ids.len() < if closed { 3 } else { 2 } {
+            return false;
+        }
+        let name = (1..=self.paths.len() + 1)
+            .map(|n| format!("path-{n}"))
+            .find(|name| self.paths.iter().all(|path| path.name != *name))
+            .unwrap_or_default();
+        self.paths.push(Path {
+            name,
+            points: ids,
+            closed,
+        });
+        self.current_path = Some(self.paths.len() - 1);
+        true
+    }
+
+    /// Add each selected point not already on the current path where it lengthens the path least.
+    ///
+    /// Returns `false` if there is no current path.
+    pub fn insert_selected_into_path(&mut self) -> bool {
+        let Some(path_idx) = self.current_path else {
+            return false;
+        };
+        let positions: HashMap<u64, egui::Pos2> = self
+            .points
+            .iter()
+            .map(|pt| (pt.id, egui::pos2(pt.x, pt.y)))
+            .collect();
+        for id in self.selected_ids() {
+            let path = &mut self.paths[path_idx];
+            if path.points.contains(&id) {
+                continue;
+            }
+            let pos = positions[&id];
+            let at = |i: usize| positions[&path.points[i]];
+            let len = path.points.len();
+            // Cost of inserting before index `i`: 0 and `len` extend an open path's ends
+            let cost = |i: usize| {
+                if i == 0 && !path.closed {
+                    pos.distance(at(0))
+                } else if i == len && !path.closed {
+                    pos.distance(at(len - 1))
+                } else {
+                    let prev = at((i + len - 1) % len);
+                    let next = at(i % len);
+                    prev.distance(pos) + pos.distance(next) - prev.distance(next)
+                }
+            };
+            let best = (0..=len)
+                .min_by(|&a, &b| cost(a).total_cmp(&cost(b)))
+                .unwrap_or(len);
+            path.points.insert(best, id);
+        }
+        true
+    }
+
+    pub fn reverse_current_path(&mut self) {
+        if let Some(idx) = self.current_path {
+            self.paths[idx].points.reverse();
+        }
+    }
+
+    pub fn toggle_current_path_closed(&mut self) {
+        if let Some(idx) = self.current_path {
+            self.paths[idx].closed = !self.paths[idx].closed;
+        }
+    }
+
+    pub fn delete_current_path(&mut self) {
+        if let Some(idx) = self.current_path.take() {
+            self.paths.remove(idx);
+        }
+    }
+
+    /// Make the next path current, going back to none after the last.
+    pub fn cycle_current_path(&mut self) {
+        self.current_path = match self.current_path {
+            None if !self.paths.is_empty() => Some(0),
+            Some(idx) if idx + 1 < self.paths.len() => Some(idx + 1),
+            _ => None,
+        };
+    }
+
+    /// Start editing the label of the first selected point.
+    pub fn edit_selected_label(&mut self) {
+        if let Some(&idx) = self.selected_indices().first() {
+            let pt = &self.points[idx];
+            self.prompt = Some(Prompt::EditLabel {
+                id: pt.id,
+                text: pt.label.clone().unwrap_or_default(),
+            });
+        }
+    }
+
+    /// Set the label of the point with `id`, removing it if `text` is blank.
+    pub fn set_label(&mut self, id: u64, text: &str) {
+        if let Some(pt) = self.points.iter_mut().find(|pt| pt.id == id) {
+            let text = text.trim();
+            pt.label = (!text.is_empty()).then(|| text.to_string());
+        }
+    }
+
+    /// Select every point whose label contains `query`, ignoring case.
+    pub fn select_matching_labels(&mut self, query: &str) {
+        let query = query.trim().to_lowercase();
+        let matches: Vec<usize> = if query.is_empty() {
+            Vec::new()
+        } else {
+            self.points
+                .iter()
+                .enumerate()
+                .filter(|&(idx, pt)| {
+                    self.is_point_interactive(idx)
+                        && pt
+                            .label
+                            .as_ref()
+                            .is_some_and(|label| label.to_lowercase().contains(&query))
+                })
+                .map(|(idx, _)| idx)
+                .collect()
+        };
+        self.selection = Selection::from_indices(matches);
+    }
+
+    /// Each edge with its two points, skipping edges whose points no longer exist.
+    #[must_use]
+    pub fn edge_endpoints(&self) -> Vec<(&Edge, &Point, &Point)> {
+        let by_id: HashMap<u64, &Point> = self.points.iter().map(|pt| (pt.id, pt)).collect();
+        self.edges
+            .iter()
+            .filter_map(|edge| Some((edge, *by_id.get(&edge.from)?, *by_id.get(&edge.to)?)))
+            .collect()
+    }
+
+    /// Add `idx` to the end of the selection, or remove it if already selected.
+    ///
+    /// A grouped point brings the rest of its group with it.
+    pub fn toggle_selected(&mut self, idx: usize) {
+        let mut indices = self.selected_indices();
+        let members = self.group_members(idx);
+        if indices.contains(&idx) {
+            indices.retain(|i| !members.contains(i));
+        } else {
+            indices.extend(members.into_iter().filter(|&i| i != idx));
+            indices.push(idx);
+        }
+        self.selection = Selection::from_indices(indices);
+    }
+
+    /// Join each selected point to the next one in selection order, skipping existing edges.
+    pub fn connect_selected(&mut self, directed: bool) {
+        let ids = self.selected_ids();
+        for pair in ids.windows(2) {
+            let edge = Edge {
+                from: pair[0],
+                to: pair[1],
+                directed,
+            };
+            if !self.edges.iter().any(|existing| existing.same_as(&edge)) {
+                self.edges.push(edge);
+            }
+        }
+    }
+
+    /// Remove every edge between two selected points.
+    pub fn disconnect_selected(&mut self) {
+        let ids = self.selected_ids();
+        self.edges
+            .retain(|edge| !(ids.contains(&edge.from) && ids.contains(&edge.to)));
+    }
+
+    /// Names of custom shapes used by points but not defined in the config, sorted and deduplicated.
+    #[must_use]
+    pub fn undefined_custom_shapes(&self, config: &Config) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .points
+            .iter()
+            .filter_map(|pt| match &pt.shape {
+                PointShape::Custom(name) if config.custom_outline(name).is_none() => {
+                    Some(name.clone())
+                }
+                _ => None,
+            })
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// The topmost point whose shape is under `pos`, or failing that one within grabbing distance.
+    #[must_use]
+    pub fn point_at_pos(&self, pos: egui::Pos2, config: &Config) -> Option<usize> {
+        self.points_at_pos(pos, config).first().copied()
+    }
+
+    /// Every point whose shape is under `pos`, topmost first and one per group, or failing that
+    /// the topmost within grabbing distance.
+    #[must_use]
+    pub fn points_at_pos(&self, pos: egui::Pos2, config: &Config) -> Vec<usize> {
+        let radius = config.point_radius;
+        let candidates: Vec<usize> = self
+            .draw_order()
+            .into_iter()
+            .rev()
+            .filter(|&idx| self.is_point_interactive(idx))
+            .collect();
+        let mut hits: Vec<usize> = candidates
+            .iter()
+            .copied()
+            .filter(|&idx| geometry::contains(&self.points[idx], config, pos))
+            .collect();
+        if hits.is_empty() {
+            hits.extend(candidates.iter().copied().find(|&idx| {
+                let pt = &self.points[idx];
+                let dx = pos.x - pt.x;
+                let dy = pos.y - pt.y;
+                (dx * dx + dy * dy).sqrt() < radius * 2.0
+            }));
+        }
+        // A group is one object, however many of its members overlap
+        let mut seen = Vec::new();
+        hits.retain(|&idx| match self.root_group(idx) {
+            Some(root) if seen.contains(&root) => false,
+            Some(root) => {
+                seen.push(root);
+                true
+            }
+            None => true,
+        });
+        hits
+    }
+
+    /// The point a click at `pos` picks: the topmost, or when clicking the same spot again,
+    /// the next one down the stack from the current selection.
+    pub fn click_target(&mut self, pos: egui::Pos2, config: &Config) -> Option<usize> {
+        let hits = self.points_at_pos(pos, config);
+        let repeat = self
+            .last_click
+            .is_some_and(|last| last.distance(pos) < CLICK_SLOP);
+        self.last_click = Some(pos);
+        let selected = self.selected_indices();
+        match hits.iter().position(|idx| selected.contains(idx)) {
+            Some(current) if repeat => Some(hits[(current + 1) % hits.len()]),
+            _ => hits.first().copied(),
+        }
+    }
+
+    /// Rebuild `points` from old indices in their new order, keeping the selection and drag on the same points.
+    fn reorder_points(&mut self, order: &[usize]) {
+        let mut new_index = vec![0; order.len()];
+        for (new, &old) in order.iter().enumerate() {
+            new_index[old] = new;
+        }
+        self.points = order.iter().map(|&old| self.points[old].clone()).collect();
+        let selected = self.selected_indices();
+        self.selection =
+            Selection::from_indices(selected.into_iter().map(|idx| new_index[idx]).collect());
+        self.dragging = self.dragging.map(|idx| new_index[idx]);
+    }
+
+    /// Sets of two or more points on one layer within `tolerance` of the set's first point, in document order.
+    /// Hidden and locked points are left out, so merging never touches them.
+    #[must_use]
+    pub fn duplicate_sets(&self, tolerance: f32) -> Vec<Vec<usize>> {
+        let mut assigned: Vec<bool> = (0..self.points.len())
+            .map(|idx| !self.is_point_interactive(idx))
+            .collect();
+        let mut sets = Vec::new();
+        for first in 0..self.points.len() {
+            if assigned[first] {
+                continue;
+            }
+            let keep = &self.points[first];
+            let set: Vec<usize> = (first..self.points.len())
+                .filter(|&idx| {
+                    let pt = &self.points[idx];
+                    !assigned[idx]
+                        && pt.layer == keep.layer
+                        && (pt.x - keep.x).hypot(pt.y - keep.y) <= tolerance
+                })
+                .collect();
+            if set.len() > 1 {
+                for &idx in &set {
+                    assigned[idx] = true;
+                }
+                sets.push(set);
+            }
+        }
+        sets
+    }
+
+    /// Delete all but the first point of each duplicate set, moving edges and paths onto the one kept.
+    /// A kept point without a label takes the first label among its duplicates. Returns how many were removed.
+    pub fn merge_duplicates(&mut self, tolerance: f32) -> usize {
+        let mut replaced: HashMap<u64, u64> = HashMap::new();
+        for set in self.duplicate_sets(tolerance) {
+            let keep = set[0];
+            if self.points[keep].label.is_none() {
+                self.points[keep].label =
+                    set.iter().find_map(|&idx| self.points[idx].label.clone());
+            }
+            for &idx in &set[1..] {
+                replaced.insert(self.points[idx].id, self.points[keep].id);
+            }
+        }
+        if replaced.is_empty() {
+            return 0;
+        }
+
+        let kept = |id: u64| replaced.get(&id).copied().unwrap_or(id);
+        let mut edges: Vec<Edge> = Vec::new();
+        for edge in std::mem::take(&mut self.edges) {
+            let edge = Edge {
+                from: kept(edge.from),
+                to: kept(edge.to),
+                directed: edge.directed,
+            };
+            if edge.from != edge.to && !edges.iter().any(|existing| existing.same_as(&edge)) {
+                edges.push(edge);
+            }
+        }
+        self.edges = edges;
+        for path in &mut self.paths {
+            for id in &mut path.points {
+                *id = kept(*id);
+            }
+            path.points.dedup();
+        }
+        self.points.retain(|pt| !replaced.contains_key(&pt.id));
+        let remaining: Vec<u64> = self.points.iter().map(|pt| pt.id).collect();
+        self.prune_paths(&remaining);
+        self.prune_groups();
+        self.selection = Selection::None;
+        self.dragging = None;
+        replaced.len()
+    }
+
+    /// Draw the selected points over everything else on their layers.
+    pub fn bring_selected_to_front(&mut self) {
+        let selected = self.selected_indices();
+        let (front, rest): (Vec<usize>, Vec<usize>) =
+            (0..self.points.len()).partition(|idx| selected.contains(idx));
+        self.reorder_points(&[rest, front].concat());
+    }
+
+    /// Draw the selected points under everything else on their layers.
+    pub fn send_selected_to_back(&mut self) {
+        let selected = self.selected_indices();
+        let (back, rest): (Vec<usize>, Vec<usize>) =
+            (0..self.points.len()).partition(|idx| selected.contains(idx));
+        self.reorder_points(&[back, rest].concat());
+    }
+
+    /// Move each selected point above the next unselected point on its layer.
+    pub fn raise_selected(&mut self) {
+        let selected = self.selected_indices();
+        let mut order: Vec<usize> = (0..self.points.len()).collect();
+        // Top down, so a run of selected points moves up together
+        for pos in (0..order.len()).rev() {
+            if !selected.contains(&order[pos]) {
+                continue;
+            }
+            let layer = &self.points[order[pos]].layer;
+            let above = (pos + 1..order.len()).find(|&p| self.points[order[p]].layer == *layer);
+            if let Some(above) = above.filter(|&p| !selected.contains(&order[p])) {
+                order[pos..=above].rotate_left(1);
+            }
+        }
+        self.reorder_points(&order);
+    }
+
+    /// Move each selected point below the next unselected point beneath it on its layer.
+    pub fn lower_selected(&mut self) {
+        let selected = self.selected_indices();
+        let mut order: Vec<usize> = (0..self.points.len()).collect();
+        for pos in 0..order.len() {
+            if !selected.contains(&order[pos]) {
+                continue;
+            }
+            let layer = &self.points[order[pos]].layer;
+            let below = (0..pos)
+                .rev()
+                .find(|&p| self.points[order[p]].layer == *layer);
+            if let Some(below) = below.filter(|&p| !selected.contains(&order[p])) {
+                order[below..=pos].rotate_right(1);
+            }
+        }
+        self.reorder_points(&order);
     }
 
     #[must_use]
@@ -85,6 +997,75 @@ impl AppState {
         }
     }
 
+    /// The bounds of every selected point together, if any are selected.
+    #[must_use]
+    pub fn selection_bounds(&self, config: &Config) -> Option<egui::Rect> {
+        let selected = self.selected_indices();
+        (!selected.is_empty()).then(|| {
+            selected
+                .into_iter()
+                .map(|idx| geometry::bounds(&self.points[idx], config))
+                .fold(egui::Rect::NOTHING, egui::Rect::union)
+        })
+    }
+
+    /// Move the selection so an edge or its centre lies on a nearby ruler guide, on each axis.
+    pub fn snap_to_ruler_guides(&mut self, config: &Config) {
+        let Some(moving) = self.selection_bounds(config) else {
+            return;
+        };
+        let positions = |vertical: bool| -> Vec<f32> {
+            self.ruler_guides
+                .iter()
+                .filter(|guide| guide.vertical == vertical)
+                .map(|guide| guide.position)
+                .collect()
+        };
+        let offset = snap::to_lines(
+            moving,
+            &positions(true),
+            &positions(false),
+            config.snap_distance,
+        );
+        self.move_selected(offset.x, offset.y);
+    }
+
+    /// The ruler guide within `tolerance` of `pos`, nearest first.
+    #[must_use]
+    pub fn ruler_guide_at(&self, pos: egui::Pos2, tolerance: f32) -> Option<usize> {
+        let distance = |guide: &RulerGuide| {
+            if guide.vertical {
+                (pos.x - guide.position).abs()
+            } else {
+                (pos.y - guide.position).abs()
+            }
+        };
+        self.ruler_guides
+            .iter()
+            .enumerate()
+            .filter(|(_, guide)| distance(guide) <= tolerance)
+            .min_by(|(_, a), (_, b)| distance(a).total_cmp(&distance(b)))
+            .map(|(idx, _)| idx)
+    }
+
+    /// The ruler guide a drag on the canvas at `pos` moves: only in normal mode, and only off every
+    /// point and any background image that can be dragged instead.
+    #[must_use]
+    pub fn ruler_guide_to_drag(
+        &self,
+        pos: egui::Pos2,
+        tolerance: f32,
+        config: &Config,
+    ) -> Option<usize> {
+        if self.interaction_mode != InteractionMode::Normal
+            || self.point_at_pos(pos, config).is_some()
+            || self.background_draggable_at(pos, config)
+        {
+            return None;
+        }
+        self.ruler_guide_at(pos, tolerance)
+    }
+
     pub fn move_selected(&mut self, dx: f32, dy: f32) {
         for idx in self.selected_indices() {
             self.points[idx].x += dx;
@@ -92,49 +1073,143 @@ impl AppState {
         }
     }
 
-    pub fn snap_to_grid(&mut self, grid_spacing: f32, radius: f32) {
+    /// Move the selection to line up with the visible points around it, recording the guides to draw.
+    pub fn snap_to_points(&mut self, config: &Config) {
+        let Some(moving) = self.selection_bounds(config) else {
+            return;
+        };
+        let selected = self.selected_indices();
+        let bounds = |idx: usize| geometry::bounds(&self.points[idx], config);
+        let others: Vec<egui::Rect> = self
+            .draw_order()
+            .into_iter()
+            .filter(|idx| !selected.contains(idx))
+            .map(bounds)
+            .collect();
+        let snap = snap::to_rects(moving, &others, config.snap_distance);
+        for idx in selected {
+            self.points[idx].x += snap.offset.x;
+            self.points[idx].y += snap.offset.y;
+        }
+        self.guides = snap.guides;
+    }
+
+    /// Move each selected point as `snap_mode` says: so the edge of its shape nearest a grid
+    /// line lies on it (on grids other than square, its centre on the nearest node), or its
+    /// centre on the nearest grid corner or cell centre.
+    pub fn snap_to_grid(&mut self, config: &Config) {
+        if config.snap_mode != SnapMode::Edge || config.grid_type != GridType::Square {
+            for idx in self.selected_indices() {
+                let pt = &mut self.points[idx];
+                let center = egui::pos2(pt.x, pt.y);
+                let snapped = match config.snap_mode {
+                    SnapMode::Edge => grid::nearest_node(config, center),
+                    SnapMode::Corner => grid::nearest_corner(config, center),
+                    SnapMode::Center => grid::nearest_cell_center(config, center),
+                };
+                pt.x = snapped.x;
+                pt.y = snapped.y;
+            }
+            return;
+        }
+        let grid_spacing = config.grid_spacing;
         for idx in self.selected_indices() {
+            let bounds = geometry::bounds(&self.points[idx], config);
             let pt = &mut self.points[idx];
 
             let snap_edge = |edge: f32| -> f32 { (edge / grid_spacing).round() * grid_spacing };
 
-            let edges = [
-                (pt.x - radius, pt.x + radius),
-                (pt.y - radius, pt.y + radius),
-            ];
+            let edges = [(bounds.min.x, bounds.max.x), (bounds.min.y, bounds.max.y)];
 
-            let snapped = edges.map(|(min, max)| {
-                let min_snap = snap_edge(min);
-                let max_snap = snap_edge(max);
-                if (min - min_snap).abs() < (max - max_snap).abs() {
-                    min_snap + radius
+            let shifts = edges.map(|(min, max)| {
+                let min_shift = snap_edge(min) - min;
+                let max_shift = snap_edge(max) - max;
+                if min_shift.abs() < max_shift.abs() {
+                    min_shift
                 } else {
-                    max_snap - radius
+                    max_shift
                 }
             });
 
-            pt.x = snapped[0];
-            pt.y = snapped[1];
+            pt.x += shifts[0];
+            pt.y += shifts[1];
         }
     }
 
+    /// Add a measuring point at `pos`, or at the centre of the point clicked.
+    pub fn add_measure_point(&mut self, pos: egui::Pos2, config: &Config) {
+        let anchor = self.point_at_pos(pos, config).map_or(pos, |idx| {
+            egui::pos2(self.points[idx].x, self.points[idx].y)
+        });
+        self.measure.push(anchor);
+    }
+
+    /// The distances between the selected points' centres, as `#from–#to: distance`, nearest first.
+    #[must_use]
+    pub fn selection_distances(&self) -> Vec<String> {
+        let selected = self.selected_indices();
+        let centres: Vec<egui::Pos2> = selected
+            .iter()
+            .map(|&idx| egui::pos2(self.points[idx].x, self.points[idx].y))
+            .collect();
+        measure::pairwise(&centres)
+            .into_iter()
+            .map(|(i, j, distance)| {
+                let (from, to) = (&self.points[selected[i]], &self.points[selected[j]]);
+                format!("#{}–#{}: {distance:.1}", from.id, to.id)
+            })
+            .collect()
+    }
+
+    /// The canvas area the background image covers, once loaded.
+    #[must_use]
+    pub fn background_rect(&self) -> Option<egui::Rect> {
+        let background = self.background.as_ref()?;
+        let texture = self.background_texture.as_ref()?;
+        Some(egui::Rect::from_min_size(
+            egui::pos2(background.x, background.y),
+            texture.size_vec2() * background.scale,
+        ))
+    }
+
+    /// Whether `pos` is on a shown, unlocked background image, which can be dragged into place.
+    #[must_use]
+    pub fn background_draggable_at(&self, pos: egui::Pos2, config: &Config) -> bool {
+        config.show_background
+            && self.background.as_ref().is_some_and(|bg| !bg.locked)
+            && self
+                .background_rect()
+                .is_some_and(|rect| rect.contains(pos))
+    }
+
     #[must_use]
     pub fn quantize_position(pos: f32, step: f32) -> f32 {
         (pos / step).round() * step
     }
 
+    /// Copy the selected points, offset by (dx, dy). Grouped points go into copies of their groups.
     pub fn clone_selected(&mut self, dx: f32, dy: f32) {
         let indices = self.selected_indices();
         let mut new_points = Vec::new();
+        let mut group_copies = HashMap::new();
 
         for idx in indices {
+            let group = self.points[idx]
+                .group
+                .map(|id| self.copy_group(id, &mut group_copies));
             let pt = &self.points[idx];
             new_points.push(Point {
                 id: self.next_id,
                 x: pt.x + dx,
                 y: pt.y + dy,
-                shape: pt.shape,
+                shape: pt.shape.clone(),
                 rotation: pt.rotation, // ADD THIS LINE
+                style: pt.style.clone(),
+                label: pt.label.clone(),
+                layer: pt.layer.clone(),
+                group,
+                locked: pt.locked,
+                hidden: pt.hidden,
             });
             self.next_id += 1;
         }
@@ -149,9 +1224,25 @@ impl AppState {
         }
     }
 
-    pub fn set_selected_shape(&mut self, shape: PointShape) {
+    pub fn set_selected_shape(&mut self, shape: &PointShape) {
+        for idx in self.selected_indices() {
+            self.points[idx].shape = shape.clone();
+        }
+    }
+
+    /// Flip whether each selected point is filled.
+    pub fn toggle_selected_fill(&mut self, config: &Config) {
         for idx in self.selected_indices() {
-            self.points[idx].shape = shape;
+            let fill = config.style_for(&self.points[idx]).fill;
+            self.points[idx].style.fill = Some(!fill);
+        }
+    }
+
+    /// Widen (or with a negative `delta`, narrow) the outline of each selected point.
+    pub fn adjust_selected_stroke(&mut self, delta: f32, config: &Config) {
+        for idx in self.selected_indices() {
+            let width = config.style_for(&self.points[idx]).stroke_width;
+            self.points[idx].style.stroke_width = Some((width + delta).max(0.0));
         }
     }
 
@@ -164,60 +1255,59 @@ impl AppState {
             return;
         }
 
+        let deleted: Vec<u64> = indices.iter().map(|&idx| self.points[idx].id).collect();
+        self.edges
+            .retain(|edge| !deleted.iter().any(|&id| edge.touches(id)));
+        let remaining: Vec<u64> = self
+            .points
+            .iter()
+            .map(|pt| pt.id)
+            .filter(|id| !deleted.contains(id))
+            .collect();
+        self.prune_paths(&remaining);
+
         let mut indices_sorted = indices.clone();
         indices_sorted.sort_by(|a, b| b.cmp(a));
 
         for idx in indices_sorted {
             self.points.remove(idx);
         }
+        self.prune_groups();
 
         if self.points.is_empty() {
             self.selection = Selection::None;
         } else {
             let max_id = self.points.iter().map(|p| p.id).max().unwrap();
             let max_idx = self.points.iter().position(|p| p.id == max_id).unwrap();
-            self.selection = Selection::Single(max_idx);
+            self.select_point(max_idx);
         }
     }
 
     #[must_use]
-    pub fn point_in_box(&self, idx: usize, rect: egui::Rect, radius: f32) -> bool {
-        let pt = &self.points[idx];
-        match pt.shape {
-            PointShape::Circle | PointShape::Square => {
-                rect.contains(egui::pos2(pt.x - radius, pt.y - radius))
-                    && rect.contains(egui::pos2(pt.x + radius, pt.y + radius))
-                    && rect.contains(egui::pos2(pt.x - radius, pt.y + radius))
-                    && rect.contains(egui::pos2(pt.x + radius, pt.y - radius))
-            }
-            PointShape::Diamond | PointShape::Semicircle => {
-                rect.contains(egui::pos2(pt.x, pt.y - radius))
-                    && rect.contains(egui::pos2(pt.x + radius, pt.y))
-                    && rect.contains(egui::pos2(pt.x, pt.y + radius))
-                    && rect.contains(egui::pos2(pt.x - radius, pt.y))
-            }
-        }
+    pub fn point_in_box(&self, idx: usize, rect: egui::Rect, config: &Config) -> bool {
+        rect.contains_rect(geometry::bounds(&self.points[idx], config))
     }
 
-    pub fn select_in_box(&mut self, rect: egui::Rect, radius: f32) {
+    /// Select the points entirely inside `rect`, taking groups only when every member is inside.
+    pub fn select_in_box(&mut self, rect: egui::Rect, config: &Config) {
         let mut selected = Vec::new();
         for (idx, _) in self.points.iter().enumerate() {
-            if self.point_in_box(idx, rect, radius) {
+            if self.is_point_interactive(idx) && self.point_in_box(idx, rect, config) {
                 selected.push(idx);
             }
         }
+        let inside = selected.clone();
+        selected.retain(|&idx| {
+            self.group_members(idx)
+                .iter()
+                .all(|member| inside.contains(member))
+        });
 
-        self.selection = if selected.is_empty() {
-            Selection::None
-        } else if selected.len() == 1 {
-            Selection::Single(selected[0])
-        } else {
-            Selection::Multiple(selected)
-        };
+        self.selection = Selection::from_indices(selected);
     }
 
     #[must_use]
-    pub fn convex_hull_offset(&self, direction: (f32, f32), radius: f32) -> (f32, f32) {
+    pub fn convex_hull_offset(&self, direction: (f32, f32), config: &Config) -> (f32, f32) {
         let indices = self.selected_indices();
         if indices.is_empty() {
             return (0.0, 0.0);
@@ -226,24 +1316,24 @@ impl AppState {
         let (dx, dy) = direction;
 
         if dx.abs() > 0.0 {
-            let (min, max) = self.bounds_along_axis(|pt| pt.x, &indices, radius);
+            let (min, max) = self.bounds_along_axis(|rect| rect.x_range(), &indices, config);
             (dx * (max - min), 0.0)
         } else {
-            let (min, max) = self.bounds_along_axis(|pt| pt.y, &indices, radius);
+            let (min, max) = self.bounds_along_axis(|rect| rect.y_range(), &indices, config);
             (0.0, dy * (max - min))
         }
     }
 
-    fn bounds_along_axis<F>(&self, axis: F, indices: &[usize], radius: f32) -> (f32, f32)
+    fn bounds_along_axis<F>(&self, axis: F, indices: &[usize], config: &Config) -> (f32, f32)
     where
-        F: Fn(&Point) -> f32,
+        F: Fn(egui::Rect) -> egui::Rangef,
     {
         let mut min = f32::MAX;
         let mut max = f32::MIN;
         for idx in indices {
-            let val = axis(&self.points[*idx]);
-            min = min.min(val - radius);
-            max = max.max(val + radius);
+            let range = axis(geometry::bounds(&self.points[*idx], config));
+            min = min.min(range.min);
+            max = max.max(range.max);
         }
         (min, max)
     }
@@ -264,7 +1354,7 @@ impl AppState {
 
             for (i, other) in self.points.iter().enumerate() {
                 let dist_sq = (other.x - search_pos.x).powi(2) + (other.y - search_pos.y).powi(2);
-                if dist_sq < (radius * 2.5).powi(2) {
+                if dist_sq < (radius * 2.5).powi(2) && self.is_point_interactive(i) {
                     candidates.push(i);
                 }
             }
@@ -277,37 +1367,93 @@ impl AppState {
             }
         }
 
-        self.selection = if all_selected.len() == 1 {
-            Selection::Single(all_selected[0])
-        } else {
-            Selection::Multiple(all_selected)
-        };
+        self.selection = Selection::from_indices(all_selected);
+    }
+
+    /// Append a typed digit to the count prefix.
+    pub fn push_count_digit(&mut self, digit: u32) {
+        let count = self.pending_count.unwrap_or(0).saturating_mul(10) + digit;
+        self.pending_count = Some(count.min(MAX_COUNT));
     }
 
     #[must_use]
-    pub fn status_text(&self) -> Option<String> {
-        if self.interaction_mode == InteractionMode::Paintbrush {
+    pub fn status_text(&self, config: &Config) -> Option<String> {
+        let mode = if self.interaction_mode == InteractionMode::Paintbrush {
             Some("Paintbrush".to_string())
         } else if self.interaction_mode == InteractionMode::BoxSelect {
             Some("Box Select".to_string())
+        } else if self.interaction_mode == InteractionMode::Measure {
+            Some("Measure".to_string())
         } else if self.pending_mode == PendingMode::Clone {
             Some("Clone mode".to_string())
         } else if self.pending_mode == PendingMode::Shape {
             Some("Shape mode".to_string())
+        } else if self.pending_mode == PendingMode::Path {
+            Some("Path mode".to_string())
+        } else if self.pending_mode == PendingMode::Layer {
+            Some("Layer mode".to_string())
+        } else if self.pending_mode == PendingMode::Record {
+            Some("Record macro".to_string())
+        } else if self.pending_mode == PendingMode::Play {
+            Some("Play macro".to_string())
+        } else if self.snap_to_grid && self.snap_to_points {
+            Some(format!(
+                "Snap to Grid ({}) and Points",
+                config.snap_mode.description()
+            ))
         } else if self.snap_to_grid {
-            Some("Snap to Grid".to_string())
+            Some(format!("Snap to Grid ({})", config.snap_mode.description()))
+        } else if self.snap_to_points {
+            Some("Snap to Points".to_string())
         } else {
             None
+        };
+        let mut parts = Vec::new();
+        if let Some(count) = self.pending_count {
+            parts.push(format!("{count} ×"));
+        }
+        if let Some(mode) = mode {
+            parts.push(mode);
+        }
+        if self.measure.len() > 1 {
+            parts.push(format!(
+                "Length {:.1}",
+                measure::chain_length(&self.measure)
+            ));
+        }
+        if let Some(idx) = self.current_path {
+            parts.push(format!("Path: {}", self.paths[idx].name));
+        }
+        if self.layers.len() > 1 {
+            parts.push(format!("Layer: {}", self.layers[self.current_layer].name));
+        }
+        let hidden = self.points.iter().filter(|pt| pt.hidden).count();
+        if hidden > 0 {
+            parts.push(format!("{hidden} hidden"));
+        }
+        if let Some((register, _)) = &self.recording {
+            parts.push(format!("Recording @{register}"));
+        }
+        if let Some(bounds) = self.selection_bounds(config) {
+            parts.push(format!("{:.0} × {:.0}", bounds.width(), bounds.height()));
+        }
+        if let Some(cursor) = self.cursor {
+            parts.push(format!("({:.0}, {:.0})", cursor.x, cursor.y));
+        }
+        if parts.is_empty() {
+            None
+        } else {
+            Some(parts.join(" "))
         }
     }
 
     #[must_use]
     pub fn get_paint_shape(&self) -> PointShape {
         match &self.selection {
-            Selection::Single(idx) => self.points[*idx].shape,
+            Selection::Single(idx) => self.points[*idx].shape.clone(),
             Selection::Multiple(indices) => {
                 if let Some(idx) = indices.first() {
-                    self.points[*idx].shape
+                    self.points[*idx].shape.clone()
                 } else {
                     PointShape::Circle
                 }
@@ -316,16 +1462,10 @@ impl AppState {
         }
     }
 
-    pub fn paint_point(
-        &mut self,
-        pos: egui::Pos2,
-        radius: f32,
-        move_step: f32,
-        grid_spacing: f32,
-        snap: bool,
-    ) {
-        let quantized_x = Self::quantize_position(pos.x, move_step);
-        let quantized_y = Self::quantize_position(pos.y, move_step);
+    pub fn paint_point(&mut self, pos: egui::Pos2, config: &Config, snap: bool) {
+        let radius = config.point_radius;
+        let quantized_x = Self::quantize_position(pos.x, config.move_step);
+        let quantized_y = Self::quantize_position(pos.y, config.move_step);
 
         if let Some(last_pos) = self.last_paint_pos {
             let dx = (quantized_x - last_pos.x).abs();
@@ -338,12 +1478,19 @@ impl AppState {
 
         let shape = self.get_paint_shape();
         let rotation = self.get_paint_rotation();
+        let style = self.get_paint_style();
         let new_point = Point {
             id: self.next_id,
             x: quantized_x,
             y: quantized_y,
             shape,
             rotation,
+            style,
+            label: None,
+            layer: self.layers[self.current_layer].name.clone(),
+            group: None,
+            locked: false,
+            hidden: false,
         };
 
         self.next_id += 1;
@@ -353,15 +1500,39 @@ impl AppState {
             let idx = self.points.len() - 1;
             let temp_selection = self.selection.clone();
             self.selection = Selection::Single(idx);
-            self.snap_to_grid(grid_spacing, radius);
+            self.snap_to_grid(config);
             self.selection = temp_selection;
         }
 
         self.last_paint_pos = Some(egui::pos2(quantized_x, quantized_y));
     }
 
+    /// Turn each selected point in place; grouped points also swing round their group's centre.
     pub fn rotate_selected(&mut self, angle: f32) {
-        for idx in self.selected_indices() {
+        let indices = self.selected_indices();
+        let mut members: HashMap<u64, Vec<egui::Pos2>> = HashMap::new();
+        for &idx in &indices {
+            if let Some(root) = self.root_group(idx) {
+                let pt = &self.points[idx];
+                members
+                    .entry(root)
+                    .or_default()
+                    .push(egui::pos2(pt.x, pt.y));
+            }
+        }
+        let centres: HashMap<u64, egui::Pos2> = members
+            .into_iter()
+            .map(|(root, positions)| (root, egui::Rect::from_points(&positions).center()))
+            .collect();
+        let turn = egui::emath::Rot2::from_angle(angle);
+
+        for idx in indices {
+            if let Some(centre) = self.root_group(idx).and_then(|root| centres.get(&root)) {
+                let pt = &mut self.points[idx];
+                let pos = *centre + turn * (egui::pos2(pt.x, pt.y) - *centre);
+                pt.x = pos.x;
+                pt.y = pos.y;
+            }
             let pt = &mut self.points[idx];
             pt.rotation += angle;
             // Normalize to 0..2π range
@@ -383,4 +1554,354 @@ impl AppState {
             Selection::None => 0.0,
         }
     }
+
+    #[must_use]
+    pub fn get_paint_style(&self) -> PointStyle {
+        match &self.selection {
+            Selection::Single(idx) => self.points[*idx].style.clone(),
+            Selection::Multiple(indices) => indices
+                .first()
+                .map(|idx| self.points[*idx].style.clone())
+                .unwrap_or_default(),
+            Selection::None => PointStyle::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AppState, InteractionMode, Selection};
+    use crate::config::Config;
+    use crate::persistence::{Document, Edge, Group, Path, RulerGuide};
+    use eframe::egui::pos2;
+
+    fn sample_state() -> AppState {
+        let mut state = AppState::new(Vec::new());
+        state.replace_document(Document::sample());
+        state
+    }
+
+    /// The sample's first three points with the first two in group 1, nested with the third in group 2.
+    fn nested_state() -> AppState {
+        let mut state = sample_state();
+        state.selection = Selection::from_indices(vec![0, 1]);
+        assert!(state.group_selected());
+        state.select_point(0);
+        state.selection = Selection::from_indices([state.selected_indices(), vec![2]].concat());
+        assert!(state.group_selected());
+        state
+    }
+
+    #[test]
+    fn replacing_the_document_clears_the_current_path() {
+        let mut state = AppState::new(Vec::new());
+        state.replace_document(Document::sample());
+        state.selection = Selection::from_indices(vec![0, 1, 2]);
+        assert!(state.create_path(false));
+        assert!(state.current_path.is_some());
+
+        // The new document has no paths at all, so the old index must not be looked up
+        state.replace_document(Document {
+            points: Vec::new(),
+            ..Document::sample()
+        });
+        assert!(state.current_path.is_none() && state.paths.is_empty());
+    }
+
+    #[test]
+    fn only_normal_mode_picks_up_ruler_guides_off_points() {
+        let config = Config::default();
+        let mut state = AppState::new(Vec::new());
+        state.replace_document(Document::sample());
+        state.ruler_guides = vec![
+            RulerGuide {
+                vertical: true,
+                position: 100.0,
+            },
+            RulerGuide {
+                vertical: false,
+                position: 200.0,
+            },
+        ];
+        // The sample's first point sits on the horizontal guide at (400, 200)
+        assert_eq!(
+            state.ruler_guide_to_drag(pos2(102.0, 50.0), 4.0, &config),
+            Some(0)
+        );
+        assert_eq!(
+            state.ruler_guide_to_drag(pos2(110.0, 50.0), 4.0, &config),
+            None
+        );
+        assert_eq!(
+            state.ruler_guide_to_drag(pos2(400.0, 201.0), 4.0, &config),
+            None
+        );
+        for mode in [
+            InteractionMode::BoxSelect,
+            InteractionMode::Paintbrush,
+            InteractionMode::Measure,
+        ] {
+            state.interaction_mode = mode;
+            assert_eq!(
+                state.ruler_guide_to_drag(pos2(102.0, 50.0), 4.0, &config),
+                None
+            );
+        }
+    }
+
+    #[test]
+    fn replacing_the_document_prunes_dangling_paths() {
+        let mut document = Document::sample();
+        let path = |name: &str, points: Vec<u64>| Path {
+            name: name.to_string(),
+            points,
+            closed: false,
+        };
+        document.paths = vec![path("kept", vec![1, 99, 3]), path("gone", vec![2, 99])];
+        let mut state = AppState::new(Vec::new());
+        state.current_path = Some(0);
+        state.replace_document(document);
+        assert_eq!(state.paths.len(), 1);
+        assert_eq!(state.paths[0].points, [1, 3]);
+        assert!(state.current_path.is_none());
+    }
+
+    #[test]
+    fn grouping_a_group_nests_it() {
+        let state = nested_state();
+        assert!(
+            state.groups
+                == [
+                    Group {
+                        id: 1,
+                        parent: Some(2)
+                    },
+                    Group {
+                        id: 2,
+                        parent: None
+                    },
+                ]
+        );
+        let groups: Vec<Option<u64>> = state.points.iter().map(|pt| pt.group).collect();
+        assert_eq!(groups, [Some(1), Some(1), Some(2), None]);
+        assert_eq!(state.group_members(0), [0, 1, 2]);
+        assert_eq!(state.group_members(2), [0, 1, 2]);
+
+        let mut state = sample_state();
+        state.selection = Selection::Single(0);
+        assert!(!state.group_selected());
+    }
+
+    #[test]
+    fn ungrouping_dissolves_one_level() {
+        let mut state = nested_state();
+        state.select_point(2);
+        assert!(state.ungroup_selected());
+        assert!(
+            state.groups
+                == [Group {
+                    id: 1,
+                    parent: None
+                }]
+        );
+        assert_eq!(state.points[2].group, None);
+        assert_eq!(state.group_members(0), [0, 1]);
+        assert_eq!(state.group_members(2), [2]);
+
+        state.select_point(0);
+        assert!(state.ungroup_selected());
+        assert!(state.groups.is_empty());
+        state.selection = Selection::Single(2);
+        assert!(!state.ungroup_selected());
+    }
+
+    #[test]
+    fn cloning_a_group_copies_it_with_fresh_ids() {
+        let mut state = nested_state();
+        state.select_point(0);
+        state.clone_selected(10.0, 0.0);
+        assert_eq!(state.points.len(), 7);
+        assert_eq!(state.selected_indices(), [4, 5, 6]);
+
+        let inner = state.points[4].group.unwrap();
+        let outer = state.points[6].group.unwrap();
+        assert_eq!(state.points[5].group, Some(inner));
+        assert!(![1, 2].contains(&inner) && ![1, 2].contains(&outer) && inner != outer);
+        let parent = |id: u64| {
+            state
+                .groups
+                .iter()
+                .find(|group| group.id == id)
+                .and_then(|group| group.parent)
+        };
+        assert_eq!(parent(inner), Some(outer));
+        assert_eq!(parent(outer), None);
+        // The originals are untouched and still a separate group
+        assert_eq!(state.groups.len(), 4);
+        assert_eq!(state.group_members(0), [0, 1, 2]);
+        assert_eq!(state.group_members(4), [4, 5, 6]);
+    }
+
+    #[test]
+    fn replacing_the_document_prunes_dangling_groups() {
+        let mut document = Document::sample();
+        document.points[0].group = Some(1);
+        document.points[1].group = Some(9);
+        document.groups = vec![
+            Group {
+                id: 1,
+                parent: Some(7),
+            },
+            Group {
+                id: 2,
+                parent: None,
+            },
+            Group {
+                id: 3,
+                parent: Some(4),
+            },
+            Group {
+                id: 4,
+                parent: Some(3),
+            },
+        ];
+        let mut state = AppState::new(Vec::new());
+        state.replace_document(document);
+        // Missing parents and groups are cleared, and empty groups, including the cycle, dropped
+        assert!(
+            state.groups
+                == [Group {
+                    id: 1,
+                    parent: None
+                }]
+        );
+        let groups: Vec<Option<u64>> = state.points.iter().map(|pt| pt.group).collect();
+        assert_eq!(groups, [Some(1), None, None, None]);
+    }
+
+    fn ids(state: &AppState) -> Vec<u64> {
+        state.points.iter().map(|pt| pt.id).collect()
+    }
+
+    #[test]
+    fn reordering_moves_the_selection_through_its_layer() {
+        let mut state = sample_state();
+        state.selection = Selection::Single(1);
+        state.raise_selected();
+        assert_eq!(ids(&state), [1, 3, 2, 4]);
+        assert_eq!(state.selected_indices(), [2]);
+        state.bring_selected_to_front();
+        assert_eq!(ids(&state), [1, 3, 4, 2]);
+        state.send_selected_to_back();
+        assert_eq!(ids(&state), [2, 1, 3, 4]);
+        state.lower_selected();
+        assert_eq!(ids(&state), [2, 1, 3, 4]);
+        assert_eq!(state.selected_indices(), [0]);
+
+        // A run of selected points moves together
+        state.selection = Selection::from_indices(vec![0, 1]);
+        state.raise_selected();
+        assert_eq!(ids(&state), [3, 2, 1, 4]);
+        assert_eq!(state.selected_indices(), [1, 2]);
+        state.lower_selected();
+        assert_eq!(ids(&state), [2, 1, 3, 4]);
+
+        // Points on other layers are stepped over
+        state.points[3].layer = "Other".to_string();
+        state.selection = Selection::Single(2);
+        state.raise_selected();
+        assert_eq!(ids(&state), [2, 1, 3, 4]);
+        state.selection = Selection::Single(3);
+        state.lower_selected();
+        assert_eq!(ids(&state), [2, 1, 3, 4]);
+    }
+
+    #[test]
+    fn clicking_the_same_spot_cycles_down_the_stack() {
+        let config = Config::default();
+        let mut state = sample_state();
+        for pt in &mut state.points[..3] {
+            pt.x = 400.0;
+            pt.y = 200.0;
+        }
+        let click = |state: &mut AppState, x: f32| {
+            let target = state.click_target(pos2(x, 200.0), &config);
+            if let Some(idx) = target {
+                state.select_point(idx);
+            }
+            target
+        };
+        assert_eq!(click(&mut state, 400.0), Some(2));
+        assert_eq!(click(&mut state, 401.0), Some(1));
+        assert_eq!(click(&mut state, 400.0), Some(0));
+        assert_eq!(click(&mut state, 400.0), Some(2));
+        // Clicking somewhere new starts again from the top
+        state.selection = Selection::Single(0);
+        assert_eq!(click(&mut state, 405.0), Some(2));
+        assert_eq!(click(&mut state, 900.0), None);
+    }
+
+    #[test]
+    fn duplicates_are_points_on_one_layer_within_the_tolerance() {
+        let mut state = sample_state();
+        // A 3-4-5 triangle puts the second point exactly 5 from the first
+        state.points[1].x = 403.0;
+        state.points[1].y = 204.0;
+        assert_eq!(state.duplicate_sets(5.0), [vec![0, 1]]);
+        assert!(state.duplicate_sets(4.99).is_empty());
+
+        state.points[2].x = 400.0;
+        state.points[2].y = 200.0;
+        assert_eq!(state.duplicate_sets(5.0), [vec![0, 1, 2]]);
+        state.points[1].layer = "Other".to_string();
+        assert_eq!(state.duplicate_sets(5.0), [vec![0, 2]]);
+    }
+
+    #[test]
+    fn merging_moves_edges_and_paths_onto_the_kept_point() {
+        let edge = |from: u64, to: u64, directed: bool| Edge { from, to, directed };
+        let path = |name: &str, points: Vec<u64>| Path {
+            name: name.to_string(),
+            points,
+            closed: false,
+        };
+        let mut state = sample_state();
+        state.points[1].x = 400.0;
+        state.points[1].y = 200.0;
+        state.points[1].label = Some("B".to_string());
+        state.edges = vec![
+            edge(1, 3, false),
+            edge(2, 3, false),
+            edge(1, 2, false),
+            edge(2, 4, true),
+        ];
+        state.paths = vec![path("through", vec![1, 2, 3]), path("from", vec![2, 4])];
+
+        assert_eq!(state.merge_duplicates(1.0), 1);
+        assert_eq!(ids(&state), [1, 3, 4]);
+        assert_eq!(state.points[0].label.as_deref(), Some("B"));
+        // The duplicate edge and the one between the merged points go
+        assert!(state.edges == [edge(1, 3, false), edge(1, 4, true)]);
+        assert_eq!(state.paths[0].points, [1, 3]);
+        assert_eq!(state.paths[1].points, [1, 4]);
+        assert_eq!(state.merge_duplicates(1.0), 0);
+    }
+
+    #[test]
+    fn hidden_and_locked_points_are_never_merged() {
+        let mut state = sample_state();
+        for pt in &mut state.points {
+            pt.x = 400.0;
+            pt.y = 200.0;
+        }
+        state.points[1].hidden = true;
+        state.points[2].locked = true;
+        assert_eq!(state.duplicate_sets(1.0), [vec![0, 3]]);
+        state.layers[0].locked = true;
+        assert!(state.duplicate_sets(1.0).is_empty());
+
+        state.layers[0].locked = false;
+        assert_eq!(state.merge_duplicates(1.0), 1);
+        assert_eq!(ids(&state), [1, 2, 3]);
+    }
 }
@@ -1,45 +1,73 @@
 //! UI panels and keyboard input handling.
 
-use crate::config::Config;
-use crate::persistence::{self, PointShape};
-use crate::state::{AppState, InteractionMode, PendingMode};
+use crate::config::{self, Config, ViewSetting};
+use crate::export;
+use crate::geometry;
+use crate::keymap::{Action, Direction, Keymap, ROTATION_STEP, STROKE_STEP};
+use crate::macros::{self, MacroStep};
+use crate::palette::Palette;
+use crate::persistence::{self, Background, Document};
+use crate::state::{AppState, InteractionMode, PendingMode, Prompt};
 use eframe::egui;
 
-pub fn show_status_bar(ctx: &egui::Context, state: &AppState) {
-    if let Some(status) = state.status_text() {
-        egui::Area::new(egui::Id::new("status"))
-            .anchor(egui::Align2::CENTER_BOTTOM, egui::vec2(0.0, -10.0))
-            .show(ctx, |ui| {
-                egui::Frame::none()
-                    .fill(egui::Color32::from_black_alpha(180))
-                    .inner_margin(8.0)
-                    .rounding(4.0)
-                    .show(ui, |ui| {
-                        ui.set_min_width(80.0);
+pub fn show_status_bar(ctx: &egui::Context, state: &mut AppState, config: &Config) {
+    let status = state.status_text(config);
+    if status.is_none() && state.notice.is_none() {
+        return;
+    }
+    egui::Area::new(egui::Id::new("status"))
+        .anchor(egui::Align2::CENTER_BOTTOM, egui::vec2(0.0, -10.0))
+        .show(ctx, |ui| {
+            egui::Frame::none()
+                .fill(egui::Color32::from_black_alpha(180))
+                .inner_margin(8.0)
+                .rounding(4.0)
+                .show(ui, |ui| {
+                    ui.set_min_width(80.0);
+                    if let Some(status) = status {
                         ui.label(
                             egui::RichText::new(status)
                                 .color(egui::Color32::WHITE)
                                 .size(16.0),
                         );
-                    });
-            });
-    }
+                    }
+                    if let Some(notice) = &state.notice {
+                        let label = egui::Label::new(
+                            egui::RichText::new(notice)
+                                .color(egui::Color32::LIGHT_RED)
+                                .size(14.0),
+                        )
+                        .sense(egui::Sense::click());
+                        if ui.add(label).on_hover_text("Click to dismiss").clicked() {
+                            state.notice = None;
+                        }
+                    }
+                });
+        });
 }
 
-pub fn show_menu(ctx: &egui::Context, state: &mut AppState) {
+pub fn show_menu(ctx: &egui::Context, state: &mut AppState, config: &Config) {
     egui::TopBottomPanel::top("menu").show(ctx, |ui| {
         egui::menu::bar(ui, |ui| {
             ui.menu_button("File", |ui| {
                 if ui.button("Save").clicked() {
-                    persistence::save_points(&state.points);
+                    persistence::save_document(&state.document());
                     ui.close_menu();
                 }
                 if ui.button("Load").clicked() {
-                    state.points = persistence::load_points();
+                    load_document(state, config);
+                    ui.close_menu();
+                }
+                if ui.button("Load Background...").clicked() {
+                    open_background_prompt(state);
                     ui.close_menu();
                 }
                 if ui.button("Reset").clicked() {
-                    state.points = persistence::load_points();
+                    state.replace_document(Document::sample());
+                    ui.close_menu();
+                }
+                if ui.button("Export SVG").clicked() {
+                    export::save_svg(&state.document(), config);
                     ui.close_menu();
                 }
                 if ui.button("Quit").clicked() {
@@ -56,7 +84,7 @@ pub fn show_menu(ctx: &egui::Context, state: &mut AppState) {
     });
 }
 
-pub fn show_tool_panel(ctx: &egui::Context, config: &Config, _state: &mut AppState) {
+pub fn show_tool_panel(ctx: &egui::Context, config: &Config, state: &mut AppState) {
     egui::SidePanel::left("tools").show(ctx, |ui| {
         ui.heading("Parameters");
         ui.separator();
@@ -72,20 +100,239 @@ pub fn show_tool_panel(ctx: &egui::Context, config: &Config, _state: &mut AppSta
         ui.label("Appearance");
         ui.label(format!("Point Radius: {}", config.point_radius));
         ui.label(format!("Grid Spacing: {}", config.grid_spacing));
+        ui.label(format!(
+            "Fill: {}",
+            if config.point_fill { "on" } else { "off" }
+        ));
+        ui.label(format!("Stroke Width: {}", config.point_stroke_width));
+        ui.label(format!("Halo Width: {}", config.selected_halo_width));
         ui.separator();
 
         ui.label("Colors");
         show_color_swatch(ui, "Background", &config.bg_color, config);
         show_color_swatch(ui, "Point", &config.point_color, config);
+        show_color_swatch(ui, "Stroke", &config.point_stroke_color, config);
         show_color_swatch(ui, "Selected", &config.selected_color, config);
         show_color_swatch(ui, "Selection Box", &config.selection_box_color, config);
         show_color_swatch(ui, "Grid", &config.grid_color, config);
+        ui.separator();
+
+        show_layers(ui, state);
+        if state.background.is_some() {
+            ui.separator();
+            show_background(ui, state);
+        }
     });
 }
 
+/// Gap between the minimap and the corner of the canvas.
+const MINIMAP_MARGIN: f32 = 10.0;
+
+/// An overview of every point and the part of the canvas in view, in the canvas's bottom right
+/// corner.
+pub fn show_minimap(ui: &egui::Ui, state: &AppState, config: &Config, canvas: egui::Rect) {
+    let size = egui::vec2(config.minimap_width, config.minimap_width * 2.0 / 3.0);
+    let rect =
+        egui::Rect::from_min_size(canvas.max - size - egui::Vec2::splat(MINIMAP_MARGIN), size);
+    let extent = state
+        .draw_order()
+        .into_iter()
+        .map(|idx| geometry::bounds(&state.points[idx], config))
+        .fold(canvas, egui::Rect::union);
+    let extent = extent.expand(extent.size().max_elem() * 0.05);
+    let scale = (size.x / extent.width()).min(size.y / extent.height());
+    let to_map = egui::emath::TSTransform::new(
+        rect.center().to_vec2() - extent.center().to_vec2() * scale,
+        scale,
+    );
+
+    let painter = ui.painter().with_clip_rect(rect);
+    painter.rect_filled(rect, 4.0, egui::Color32::from_white_alpha(220));
+    let point_color = Config::colour(&config.point_color);
+    let selected_color = Config::colour(&config.selected_color);
+    let selected = state.selected_indices();
+    for idx in state.draw_order() {
+        let pt = &state.points[idx];
+        let color = if selected.contains(&idx) {
+            selected_color
+        } else {
+            point_color
+        };
+        painter.circle_filled(to_map * egui::pos2(pt.x, pt.y), 1.5, color);
+    }
+    let viewport_stroke = egui::Stroke::new(1.0, Config::colour(&config.selection_box_color));
+    painter.rect_stroke(to_map * canvas, 0.0, viewport_stroke);
+    painter.rect_stroke(rect, 4.0, egui::Stroke::new(1.0, egui::Color32::GRAY));
+}
+
+/// Placement, opacity and lock for the background image.
+fn show_background(ui: &mut egui::Ui, state: &mut AppState) {
+    ui.label("Background");
+    let Some(background) = &mut state.background else {
+        return;
+    };
+    ui.label(&background.path);
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        ui.label("Position:");
+        changed |= ui.add(egui::DragValue::new(&mut background.x)).changed();
+        changed |= ui.add(egui::DragValue::new(&mut background.y)).changed();
+    });
+    ui.horizontal(|ui| {
+        ui.label("Scale:");
+        let slider = egui::Slider::new(&mut background.scale, 0.01..=10.0).logarithmic(true);
+        changed |= ui.add(slider).changed();
+    });
+    ui.horizontal(|ui| {
+        ui.label("Opacity:");
+        changed |= ui
+            .add(egui::Slider::new(&mut background.opacity, 0.0..=1.0))
+            .changed();
+    });
+    changed |= ui.checkbox(&mut background.locked, "🔒 Locked").changed();
+    if ui.button("Remove").clicked() {
+        state.background = None;
+        changed = true;
+    }
+    if changed {
+        persistence::save_document(&state.document());
+    }
+}
+
+/// Ask for the background image's file, starting from the current one.
+fn open_background_prompt(state: &mut AppState) {
+    let path = state
+        .background
+        .as_ref()
+        .map(|background| background.path.clone())
+        .unwrap_or_default();
+    state.prompt = Some(Prompt::BackgroundPath { path });
+}
+
+/// Trace over the image at `path`, keeping the current placement if there is one; an empty path
+/// removes the background.
+fn set_background(state: &mut AppState, path: &str) {
+    if path.is_empty() {
+        state.background = None;
+    } else if let Some(background) = &mut state.background {
+        background.path = path.to_string();
+    } else {
+        state.background = Some(Background::new(path));
+    }
+    persistence::save_document(&state.document());
+}
+
+/// Take an image file dropped on the window as the background, and load the background image
+/// whenever its file changes.
+pub fn sync_background(ctx: &egui::Context, state: &mut AppState) {
+    let dropped = ctx.input(|i| {
+        i.raw
+            .dropped_files
+            .iter()
+            .find_map(|file| file.path.clone())
+    });
+    if let Some(path) = dropped {
+        set_background(state, &path.to_string_lossy());
+    }
+
+    let path = state
+        .background
+        .as_ref()
+        .map(|background| background.path.clone());
+    if path == state.loaded_background {
+        return;
+    }
+    state.background_texture = None;
+    if let Some(path) = &path {
+        match persistence::load_image(path) {
+            Ok(image) => {
+                let texture = ctx.load_texture(path, image, egui::TextureOptions::LINEAR);
+                state.background_texture = Some(texture);
+            }
+            Err(e) => state.notice = Some(e),
+        }
+    }
+    state.loaded_background = path;
+}
+
+/// The layer list, top layer first, with the controls for each and for the current layer.
+fn show_layers(ui: &mut egui::Ui, state: &mut AppState) {
+    ui.label("Layers");
+    let mut changed = false;
+    for idx in (0..state.layers.len()).rev() {
+        ui.horizontal(|ui| {
+            let current = idx == state.current_layer;
+            let layer = &mut state.layers[idx];
+            changed |= ui
+                .checkbox(&mut layer.visible, "")
+                .on_hover_text("Visible")
+                .changed();
+            changed |= ui
+                .checkbox(&mut layer.locked, "🔒")
+                .on_hover_text("Locked")
+                .changed();
+            if ui.selectable_label(current, &layer.name).clicked() {
+                state.current_layer = idx;
+            }
+        });
+    }
+
+    // The name being typed lives in egui's memory until the field loses focus
+    let name_id = egui::Id::new("layer-name");
+    let mut name = ui
+        .data(|data| data.get_temp::<String>(name_id))
+        .unwrap_or_else(|| state.layers[state.current_layer].name.clone());
+    ui.horizontal(|ui| {
+        ui.label("Name:");
+        let response = ui.text_edit_singleline(&mut name);
+        if response.has_focus() {
+            ui.data_mut(|data| data.insert_temp(name_id, name.clone()));
+        } else {
+            ui.data_mut(|data| data.remove::<String>(name_id));
+        }
+        if response.lost_focus() && state.rename_current_layer(&name) {
+            changed = true;
+        }
+    });
+    ui.horizontal(|ui| {
+        ui.label("Opacity:");
+        let opacity = &mut state.layers[state.current_layer].opacity;
+        changed |= ui.add(egui::Slider::new(opacity, 0.0..=1.0)).changed();
+    });
+    ui.horizontal(|ui| {
+        if ui.button("New").clicked() {
+            state.add_layer();
+            changed = true;
+        }
+        if ui.button("⬆").on_hover_text("Raise").clicked() {
+            state.move_current_layer(1);
+            changed = true;
+        }
+        if ui.button("⬇").on_hover_text("Lower").clicked() {
+            state.move_current_layer(-1);
+            changed = true;
+        }
+        if ui
+            .add_enabled(state.layers.len() > 1, egui::Button::new("Delete"))
+            .clicked()
+        {
+            changed |= state.delete_current_layer();
+        }
+    });
+    if ui.button("Move selection here").clicked() {
+        state.move_selected_to_layer();
+        changed = true;
+    }
+
+    if changed {
+        state.deselect_non_interactive();
+        persistence::save_document(&state.document());
+    }
+}
+
 fn show_color_swatch(ui: &mut egui::Ui, label: &str, hex: &str, _config: &Config) {
     ui.horizontal(|ui| {
-        let color = Config::parse_colour(hex);
+        let color = Config::colour(hex);
         ui.label(format!("{label}: "));
         let size = egui::vec2(16.0, 16.0);
         let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
@@ -94,215 +341,650 @@ fn show_color_swatch(ui: &mut egui::Ui, label: &str, hex: &str, _config: &Config
     });
 }
 
-pub fn show_help_window(ctx: &egui::Context, state: &mut AppState) {
+pub fn show_help_window(ctx: &egui::Context, state: &mut AppState, keymap: &Keymap) {
     if state.show_help {
         egui::Window::new("Keyboard Shortcuts")
             .open(&mut state.show_help)
             .show(ctx, |ui| {
-                ui.heading("Interaction Modes");
-                ui.label("B: Toggle box select");
-                ui.label("  Arrow keys: Expand selection");
-                ui.label("P: Toggle paintbrush mode");
-                ui.label("  Click/drag: Paint points");
-
-                ui.add_space(8.0);
-                ui.separator();
-                ui.add_space(8.0);
-
-                ui.heading("Movement");
-                ui.label("Arrow Keys: Move selected point(s)");
-                ui.label("Shift + Arrow: Move by large step");
-
-                ui.add_space(8.0);
-                ui.separator();
-                ui.add_space(8.0);
-
-                ui.heading("Cloning");
-                ui.label("C then C: Clone on top");
-                ui.label("C then Arrow: Clone adjacent");
-
-                ui.add_space(8.0);
-                ui.separator();
-                ui.add_space(8.0);
-
-                ui.heading("Shapes");
-                ui.label("S then C: Set shape to Circle");
-                ui.label("S then S: Set shape to Square");
-                ui.label("S then D: Set shape to Diamond");
-                ui.label("S then H: Set shape to Semicircle");
-
-                ui.add_space(8.0);
-                ui.separator();
-                ui.add_space(8.0);
-
-                ui.heading("Rotation");
-                ui.label("R: Rotate semicircle clockwise (15°)");
-                ui.label("Shift + R: Rotate semicircle counter-clockwise (15°)");
-
-                ui.add_space(8.0);
-                ui.separator();
-                ui.add_space(8.0);
-
-                ui.heading("Other");
-                ui.label("D: Delete selected");
-                ui.label("G: Toggle snap-to-grid");
-                ui.label("V then G: Toggle grid visibility");
-                ui.label("Ctrl + Scroll: Zoom");
-                ui.label("Ctrl+S: Save");
-                ui.label("Ctrl+O: Load");
-                ui.label("Ctrl+R: Reset");
-                ui.label("?: Show/hide help");
-                ui.label("Q or Escape: Quit");
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    for (category, bindings) in keymap.by_category() {
+                        ui.heading(category);
+                        for binding in bindings {
+                            ui.label(format!(
+                                "{}: {}",
+                                binding.keys_label(),
+                                binding.action.description()
+                            ));
+                        }
+
+                        ui.add_space(8.0);
+                        ui.separator();
+                        ui.add_space(8.0);
+                    }
+
+                    ui.heading("Mouse");
+                    ui.label("Click/drag: Select and move points");
+                    ui.label("Shift + Click: Add to selection, in order");
+                    ui.label("Ctrl + Scroll: Zoom");
+                    ui.label("Drag from a ruler: Add a guide");
+                    ui.label("Drag background: Move it, unless locked");
+                    ui.label("Measure mode: Click to measure, right-click to start over");
+                });
             });
     }
 }
 
-fn toggle_mode(current: InteractionMode, target: InteractionMode) -> InteractionMode {
-    if current == target {
-        InteractionMode::Normal
-    } else {
-        target
+/// The label editor or label search, whichever prompt is open.
+///
+/// Enter saves an edited label and Escape discards it; a search selects matches as you type.
+pub fn show_prompt(ctx: &egui::Context, state: &mut AppState, config: &Config) {
+    let Some(prompt) = &mut state.prompt else {
+        return;
+    };
+    let (escape, enter) = ctx.input_mut(|i| {
+        (
+            i.consume_key(egui::Modifiers::NONE, egui::Key::Escape),
+            i.consume_key(egui::Modifiers::NONE, egui::Key::Enter),
+        )
+    });
+
+    let window = egui::Window::new("Prompt")
+        .title_bar(false)
+        .resizable(false);
+    let (window, text, hint) = match prompt {
+        Prompt::EditLabel { id, text } => {
+            // Just below the point being labelled
+            let below = state
+                .points
+                .iter()
+                .find(|pt| pt.id == *id)
+                .map(|pt| geometry::bounds(pt, config).left_bottom());
+            (
+                window.fixed_pos(below.unwrap_or_default()),
+                text,
+                "Label text (empty to remove)",
+            )
+        }
+        Prompt::FindLabel { query } => (
+            window.anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 40.0)),
+            query,
+            "Search labels...",
+        ),
+        Prompt::BackgroundPath { path } => (
+            window.anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 40.0)),
+            path,
+            "PNG or JPEG file (empty to remove)",
+        ),
+    };
+    let mut changed = false;
+    window.show(ctx, |ui| {
+        let field = ui.add(
+            egui::TextEdit::singleline(text)
+                .hint_text(hint)
+                .desired_width(200.0),
+        );
+        field.request_focus();
+        changed = field.changed();
+    });
+
+    match state.prompt.clone() {
+        Some(Prompt::EditLabel { id, text }) if enter => {
+            state.set_label(id, &text);
+            persistence::save_document(&state.document());
+        }
+        Some(Prompt::FindLabel { query }) if changed => state.select_matching_labels(&query),
+        Some(Prompt::BackgroundPath { path }) if enter => set_background(state, path.trim()),
+        _ => {}
+    }
+    if escape || enter {
+        state.prompt = None;
     }
 }
 
-fn toggle_pending(current: PendingMode, target: PendingMode) -> PendingMode {
-    if current == target {
-        PendingMode::None
+/// Number of matching actions listed in the command palette at once.
+const PALETTE_ROWS: usize = 12;
+
+pub fn show_command_palette(
+    ctx: &egui::Context,
+    state: &mut AppState,
+    config: &mut Config,
+    keymap: &Keymap,
+) {
+    let Some(palette) = &mut state.palette else {
+        return;
+    };
+
+    // Consume navigation keys so they don't also reach `handle_keyboard`
+    let (escape, enter, up, down) = ctx.input_mut(|i| {
+        (
+            i.consume_key(egui::Modifiers::NONE, egui::Key::Escape),
+            i.consume_key(egui::Modifiers::NONE, egui::Key::Enter),
+            i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowUp),
+            i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowDown),
+        )
+    });
+
+    let entries = palette.entries(keymap);
+    if down {
+        palette.selected = (palette.selected + 1).min(entries.len().saturating_sub(1));
+    }
+    if up {
+        palette.selected = palette.selected.saturating_sub(1);
+    }
+
+    let mut chosen = if enter {
+        entries
+            .get(palette.selected)
+            .map(|entry| entry.action.clone())
     } else {
-        target
+        None
+    };
+
+    egui::Window::new("Command Palette")
+        .title_bar(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 40.0))
+        .show(ctx, |ui| {
+            ui.set_width(400.0);
+            let query = ui.add(
+                egui::TextEdit::singleline(&mut palette.query)
+                    .hint_text("Type a command...")
+                    .desired_width(f32::INFINITY),
+            );
+            query.request_focus();
+            if query.changed() {
+                palette.selected = 0;
+            }
+            ui.separator();
+
+            let first = palette.selected.saturating_sub(PALETTE_ROWS - 1);
+            for (i, entry) in entries.iter().enumerate().skip(first).take(PALETTE_ROWS) {
+                ui.horizontal(|ui| {
+                    let row =
+                        ui.selectable_label(i == palette.selected, entry.action.description());
+                    if row.clicked() {
+                        chosen = Some(entry.action.clone());
+                    }
+                    if let Some(keys) = &entry.keys {
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                            ui.weak(keys);
+                        });
+                    }
+                });
+            }
+            if entries.is_empty() {
+                ui.weak("No matching commands");
+            }
+        });
+
+    if escape || chosen.is_some() {
+        state.palette = None;
+    }
+    if let Some(action) = chosen {
+        run_action(ctx, state, config, action, 1);
     }
 }
 
-fn handle_arrow_keys<F>(ctx: &egui::Context, mut handler: F)
-where
-    F: FnMut(f32, f32),
-{
-    if ctx.input(|i| i.key_pressed(egui::Key::ArrowLeft)) {
-        handler(-1.0, 0.0);
-    }
-    if ctx.input(|i| i.key_pressed(egui::Key::ArrowRight)) {
-        handler(1.0, 0.0);
+/// Load `points.json`, reporting a parse failure or undefined custom shapes in the status bar.
+pub fn load_document(state: &mut AppState, config: &Config) {
+    match persistence::load_document() {
+        Ok(document) => {
+            state.replace_document(document);
+            let undefined = state.undefined_custom_shapes(config);
+            state.notice = (!undefined.is_empty())
+                .then(|| format!("Undefined custom shapes: {}", undefined.join(", ")));
+        }
+        Err(e) => state.notice = Some(e),
     }
-    if ctx.input(|i| i.key_pressed(egui::Key::ArrowUp)) {
-        handler(0.0, -1.0);
+}
+
+/// Replay the steps in `register` `count` times, relative to the current selection.
+fn play_macro(
+    ctx: &egui::Context,
+    state: &mut AppState,
+    config: &mut Config,
+    register: char,
+    count: u32,
+) {
+    let Some(steps) = state.macros.get(&register).cloned() else {
+        state.notice = Some(format!("Macro register @{register} is empty"));
+        return;
+    };
+    for _ in 0..count {
+        for step in &steps {
+            run_action(ctx, state, config, step.action.clone(), step.count);
+        }
     }
-    if ctx.input(|i| i.key_pressed(egui::Key::ArrowDown)) {
-        handler(0.0, 1.0);
+}
+
+fn digit_value(key: egui::Key) -> Option<u32> {
+    let digits = [
+        egui::Key::Num0,
+        egui::Key::Num1,
+        egui::Key::Num2,
+        egui::Key::Num3,
+        egui::Key::Num4,
+        egui::Key::Num5,
+        egui::Key::Num6,
+        egui::Key::Num7,
+        egui::Key::Num8,
+        egui::Key::Num9,
+    ];
+    digits
+        .iter()
+        .position(|&digit| digit == key)
+        .and_then(|n| u32::try_from(n).ok())
+}
+
+fn toggle_mode(current: InteractionMode, target: InteractionMode) -> InteractionMode {
+    if current == target {
+        InteractionMode::Normal
+    } else {
+        target
     }
 }
 
-pub fn handle_keyboard(ctx: &egui::Context, state: &mut AppState, config: &mut Config) {
-    let shift = ctx.input(|i| i.modifiers.shift);
-    let step = if shift {
-        config.move_step_large
+/// Most pairwise distances listed at once by `measure-selection`.
+const MAX_DISTANCES: usize = 15;
+
+/// Enter `target` mode, or leave it if already there, dropping any half-drawn box, stroke or
+/// measurement.
+fn switch_mode(state: &mut AppState, target: InteractionMode) {
+    state.interaction_mode = toggle_mode(state.interaction_mode, target);
+    state.box_select_start = None;
+    state.box_select_end = None;
+    state.last_paint_pos = None;
+    state.measure.clear();
+}
+
+/// List the distances between the selected points in the notice area.
+fn measure_selection(state: &mut AppState) {
+    let distances = state.selection_distances();
+    state.notice = Some(if distances.is_empty() {
+        "Select two or more points to measure between".to_string()
+    } else if distances.len() > MAX_DISTANCES {
+        let more = distances.len() - MAX_DISTANCES;
+        format!(
+            "{}\n… and {more} more",
+            distances[..MAX_DISTANCES].join("\n")
+        )
     } else {
-        config.move_step
+        distances.join("\n")
+    });
+}
+
+/// Hide or lock the selection, or bring back every hidden or locked point, and save.
+fn hide_or_lock(state: &mut AppState, action: &Action) {
+    let (count, verb) = match action {
+        Action::HideSelected => (state.hide_selected(), "Hid"),
+        Action::UnhideAll => (state.unhide_all(), "Unhid"),
+        Action::LockSelected => (state.lock_selected(), "Locked"),
+        Action::UnlockAll => (state.unlock_all(), "Unlocked"),
+        _ => return,
     };
+    state.notice = Some(format!("{verb} {count} point(s)"));
+    if count > 0 {
+        persistence::save_document(&state.document());
+    }
+}
 
-    if ctx.input(|i| i.key_pressed(egui::Key::R)) {
-        // Rotation angle: 45 degrees = π/4 radians
-        let rotation_angle = std::f32::consts::PI / 4.0;
-        if shift {
-            // Counter-clockwise
-            state.rotate_selected(-rotation_angle);
-        } else {
-            // Clockwise
-            state.rotate_selected(rotation_angle);
+/// Move the selection `count` steps, or in box select mode grow it `count` times.
+#[allow(clippy::cast_precision_loss)]
+fn move_selection(state: &mut AppState, config: &Config, dir: Direction, step: f32, count: u32) {
+    let (dx, dy) = dir.offset();
+    if state.interaction_mode == InteractionMode::BoxSelect {
+        for _ in 0..count {
+            state.expand_selection_box((dx, dy), config.point_radius);
+        }
+    } else {
+        let step = step * count as f32;
+        state.move_selected(dx * step, dy * step);
+        if state.snap_to_grid {
+            state.snap_to_grid(config);
         }
-        persistence::save_points(&state.points);
     }
+}
 
-    if ctx.input(|i| i.key_pressed(egui::Key::G)) {
-        if state.pending_mode == PendingMode::View {
-            config.grid_enabled = !config.grid_enabled;
-            state.pending_mode = PendingMode::None;
-        } else {
-            state.snap_to_grid = !state.snap_to_grid;
+/// Write the macro registers to the config file's `[macros]` table.
+fn save_macros(state: &mut AppState, config: &mut Config) {
+    let table = macros::to_table(&state.macros);
+    match Config::write_macros(config::CONFIG_FILE, &table) {
+        Ok(()) => config.macros = table,
+        Err(e) => state.notice = Some(e),
+    }
+}
+
+/// Apply a rotation, style, edge, stacking or group command to the selected points and save the result.
+#[allow(clippy::cast_precision_loss)]
+fn edit_selected(state: &mut AppState, config: &Config, action: &Action, count: u32) {
+    match action {
+        Action::RotateClockwise => state.rotate_selected(ROTATION_STEP * count as f32),
+        Action::RotateCounterClockwise => state.rotate_selected(-ROTATION_STEP * count as f32),
+        Action::ToggleFill => state.toggle_selected_fill(config),
+        Action::StrokeWider => state.adjust_selected_stroke(STROKE_STEP * count as f32, config),
+        Action::StrokeThinner => {
+            state.adjust_selected_stroke(-STROKE_STEP * count as f32, config);
+        }
+        Action::Connect => state.connect_selected(false),
+        Action::ConnectDirected => state.connect_selected(true),
+        Action::Disconnect => state.disconnect_selected(),
+        Action::BringToFront => state.bring_selected_to_front(),
+        Action::SendToBack => state.send_selected_to_back(),
+        Action::Raise => state.raise_selected(),
+        Action::Lower => state.lower_selected(),
+        Action::Group => {
+            if !state.group_selected() {
+                state.notice = Some("Select at least two points or groups to group".into());
+                return;
+            }
         }
+        Action::Ungroup => {
+            if !state.ungroup_selected() {
+                state.notice = Some("Nothing selected is grouped".into());
+                return;
+            }
+        }
+        _ => return,
     }
+    persistence::save_document(&state.document());
+}
 
-    if ctx.input(|i| i.key_pressed(egui::Key::V)) {
-        state.pending_mode = PendingMode::View;
-    } else if !ctx.input(|i| i.key_down(egui::Key::G)) {
-        state.pending_mode = PendingMode::None;
+/// Apply a path command, saving any change and reporting problems in the status bar.
+fn edit_path(ctx: &egui::Context, state: &mut AppState, config: &Config, action: &Action) {
+    if !matches!(action, Action::CreatePolyline | Action::CreatePolygon)
+        && state.current_path.is_none()
+    {
+        state.notice = Some("No current path: create one, or select one with path-next".into());
+        return;
     }
+    match action {
+        Action::CreatePolyline | Action::CreatePolygon => {
+            let closed = *action == Action::CreatePolygon;
+            if !state.create_path(closed) {
+                let needed = if closed { 3 } else { 2 };
+                state.notice = Some(format!("Select at least {needed} points to make a path"));
+                return;
+            }
+        }
+        Action::InsertIntoPath => {
+            state.insert_selected_into_path();
+        }
+        Action::ReversePath => state.reverse_current_path(),
+        Action::ToggleClosePath => state.toggle_current_path_closed(),
+        Action::NextPath => state.cycle_current_path(),
+        Action::DeletePath => state.delete_current_path(),
+        Action::CopyPathSvg => {
+            if let Some(idx) = state.current_path {
+                let path = &state.paths[idx];
+                ctx.copy_text(export::path_element(path, &state.points, config));
+                state.notice = Some(format!("Copied {} as an SVG <path>", path.name));
+            }
+            return;
+        }
+        _ => return,
+    }
+    persistence::save_document(&state.document());
+}
 
-    if ctx.input(|i| i.key_pressed(egui::Key::Questionmark)) {
-        state.show_help = !state.show_help;
+fn edit_layer(state: &mut AppState, action: &Action) {
+    match action {
+        Action::NewLayer => state.add_layer(),
+        Action::PreviousLayer => state.cycle_current_layer(-1),
+        Action::NextLayer => state.cycle_current_layer(1),
+        Action::RaiseLayer => state.move_current_layer(1),
+        Action::LowerLayer => state.move_current_layer(-1),
+        Action::ToggleLayerVisible => state.toggle_current_layer_visible(),
+        Action::ToggleLayerLock => state.toggle_current_layer_locked(),
+        Action::MoveToLayer => state.move_selected_to_layer(),
+        Action::DeleteLayer => {
+            if !state.delete_current_layer() {
+                state.notice = Some("Can't delete the only layer".into());
+                return;
+            }
+        }
+        _ => return,
     }
+    state.deselect_non_interactive();
+    persistence::save_document(&state.document());
+}
 
-    if ctx.input(|i| i.key_pressed(egui::Key::Q) || i.key_pressed(egui::Key::Escape)) {
-        ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+/// Add the action to the macro being recorded, if any and if it can be replayed.
+fn record_step(state: &mut AppState, action: &Action, count: u32) {
+    if let Some((_, steps)) = &mut state.recording {
+        if macros::is_recordable(action) {
+            steps.push(MacroStep {
+                action: action.clone(),
+                count,
+            });
+        }
     }
+}
 
-    if ctx.input(|i| i.modifiers.ctrl && i.key_pressed(egui::Key::S)) {
-        persistence::save_points(&state.points);
+/// Flip one of the canvas display settings, or reset the view.
+fn toggle_view(state: &mut AppState, config: &mut Config, action: &Action) {
+    let setting = match action {
+        Action::ToggleGrid => Some(ViewSetting::Grid),
+        Action::ToggleAxes => Some(ViewSetting::Axes),
+        Action::ToggleRulers => Some(ViewSetting::Rulers),
+        Action::ToggleMinimap => Some(ViewSetting::Minimap),
+        Action::ToggleBackground => Some(ViewSetting::Background),
+        Action::CycleSnapMode => Some(ViewSetting::SnapMode),
+        Action::ToggleIds => Some(ViewSetting::Ids),
+        Action::ToggleDuplicates => Some(ViewSetting::Duplicates),
+        _ => None,
+    };
+    if let Some(setting) = setting {
+        if !state.toggled_view.contains(&setting) {
+            state.toggled_view.push(setting);
+        }
     }
+    match action {
+        Action::ToggleSnap => state.snap_to_grid = !state.snap_to_grid,
+        Action::TogglePointSnap => state.snap_to_points = !state.snap_to_points,
+        Action::ToggleGrid => config.grid_enabled = !config.grid_enabled,
+        Action::ToggleAxes => config.show_axes = !config.show_axes,
+        Action::ToggleRulers => config.show_rulers = !config.show_rulers,
+        Action::ToggleMinimap => config.show_minimap = !config.show_minimap,
+        Action::ToggleBackground => config.show_background = !config.show_background,
+        Action::CycleSnapMode => config.snap_mode = config.snap_mode.next(),
+        Action::ToggleIds => config.show_ids = !config.show_ids,
+        Action::ToggleDuplicates => {
+            config.show_duplicates = !config.show_duplicates;
+            if config.show_duplicates {
+                let sets = state.duplicate_sets(config.duplicate_tolerance);
+                let points: usize = sets.iter().map(Vec::len).sum();
+                state.notice = Some(format!(
+                    "{} sets of duplicates, {points} points",
+                    sets.len()
+                ));
+            }
+        }
+        _ => {}
+    }
+}
 
-    if ctx.input(|i| i.modifiers.ctrl && i.key_pressed(egui::Key::O)) {
-        state.points = persistence::load_points();
+fn merge_duplicates(state: &mut AppState, config: &Config) {
+    let removed = state.merge_duplicates(config.duplicate_tolerance);
+    state.notice = Some(format!("Merged away {removed} duplicate point(s)"));
+    if removed > 0 {
+        persistence::save_document(&state.document());
     }
+}
 
-    if ctx.input(|i| i.modifiers.ctrl && i.key_pressed(egui::Key::R)) {
-        state.points = persistence::load_points();
+/// Run an action against the app state; every key binding goes through here.
+///
+/// `count` repeats movement, cloning, rotation and stroke width (e.g. `12` then an arrow moves
+/// 12 steps) and is ignored by other actions.
+pub fn run_action(
+    ctx: &egui::Context,
+    state: &mut AppState,
+    config: &mut Config,
+    action: Action,
+    count: u32,
+) {
+    let count = count.max(1);
+    record_step(state, &action, count);
+
+    match action {
+        Action::Pending(mode) => state.pending_mode = mode,
+        Action::Move(dir) => move_selection(state, config, dir, config.move_step, count),
+        Action::MoveLarge(dir) => {
+            move_selection(state, config, dir, config.move_step_large, count);
+        }
+        Action::CloneInPlace => {
+            for _ in 0..count {
+                state.clone_selected(0.0, 0.0);
+            }
+        }
+        Action::CloneAdjacent(dir) => {
+            for _ in 0..count {
+                let (dx, dy) = state.convex_hull_offset(dir.offset(), config);
+                state.clone_selected(dx, dy);
+            }
+        }
+        Action::SetShape(shape) => state.set_selected_shape(&shape),
+        Action::RotateClockwise
+        | Action::RotateCounterClockwise
+        | Action::ToggleFill
+        | Action::StrokeWider
+        | Action::StrokeThinner
+        | Action::Connect
+        | Action::ConnectDirected
+        | Action::Disconnect
+        | Action::BringToFront
+        | Action::SendToBack
+        | Action::Raise
+        | Action::Lower
+        | Action::Group
+        | Action::Ungroup => edit_selected(state, config, &action, count),
+        Action::CreatePolyline
+        | Action::CreatePolygon
+        | Action::InsertIntoPath
+        | Action::ReversePath
+        | Action::ToggleClosePath
+        | Action::NextPath
+        | Action::DeletePath
+        | Action::CopyPathSvg => edit_path(ctx, state, config, &action),
+        Action::NewLayer
+        | Action::PreviousLayer
+        | Action::NextLayer
+        | Action::RaiseLayer
+        | Action::LowerLayer
+        | Action::ToggleLayerVisible
+        | Action::ToggleLayerLock
+        | Action::MoveToLayer
+        | Action::DeleteLayer => edit_layer(state, &action),
+        Action::ToggleSnap
+        | Action::TogglePointSnap
+        | Action::ToggleGrid
+        | Action::ToggleAxes
+        | Action::ToggleRulers
+        | Action::ToggleMinimap
+        | Action::ToggleBackground
+        | Action::CycleSnapMode
+        | Action::ToggleIds
+        | Action::ToggleDuplicates => {
+            toggle_view(state, config, &action);
+        }
+        Action::MergeDuplicates => merge_duplicates(state, config),
+        Action::EditLabel => state.edit_selected_label(),
+        Action::FindLabel => {
+            state.prompt = Some(Prompt::FindLabel {
+                query: String::new(),
+            });
+        }
+        Action::ToggleBoxSelect => switch_mode(state, InteractionMode::BoxSelect),
+        Action::TogglePaintbrush => switch_mode(state, InteractionMode::Paintbrush),
+        Action::ToggleMeasure => switch_mode(state, InteractionMode::Measure),
+        Action::MeasureSelection => measure_selection(state),
+        Action::HideSelected | Action::UnhideAll | Action::LockSelected | Action::UnlockAll => {
+            hide_or_lock(state, &action);
+        }
+        Action::Delete => state.delete_selected(),
+        Action::Save => persistence::save_document(&state.document()),
+        Action::Load => load_document(state, config),
+        Action::LoadBackground => open_background_prompt(state),
+        Action::Reset => state.replace_document(Document::sample()),
+        Action::ExportSvg => export::save_svg(&state.document(), config),
+        Action::ToggleHelp => state.show_help = !state.show_help,
+        Action::CommandPalette => state.palette = Some(Palette::default()),
+        Action::RecordMacro => {
+            if let Some((register, steps)) = state.recording.take() {
+                state.macros.insert(register, steps);
+            } else {
+                state.pending_mode = PendingMode::Record;
+            }
+        }
+        Action::PlayMacro => state.pending_mode = PendingMode::Play,
+        Action::SaveMacros => save_macros(state, config),
+        Action::Quit => ctx.send_viewport_cmd(egui::ViewportCommand::Close),
     }
+}
 
-    if ctx.input(|i| i.key_pressed(egui::Key::X)) {
-        state.delete_selected();
+pub fn handle_keyboard(
+    ctx: &egui::Context,
+    state: &mut AppState,
+    config: &mut Config,
+    keymap: &Keymap,
+) {
+    // Leave typing to focused text fields such as the command palette
+    if state.palette.is_some() || state.prompt.is_some() || ctx.wants_keyboard_input() {
+        return;
     }
 
-    if ctx.input(|i| i.key_pressed(egui::Key::B)) {
-        state.interaction_mode = toggle_mode(state.interaction_mode, InteractionMode::BoxSelect);
-        if state.interaction_mode == InteractionMode::Normal {
-            state.box_select_start = None;
-            state.box_select_end = None;
+    let presses: Vec<(egui::Key, egui::Modifiers)> = ctx.input(|i| {
+        i.events
+            .iter()
+            .filter_map(|event| match event {
+                egui::Event::Key {
+                    key,
+                    pressed: true,
+                    modifiers,
+                    ..
+                } => Some((*key, *modifiers)),
+                _ => None,
+            })
+            .collect()
+    });
+
+    for (key, modifiers) in presses {
+        if matches!(state.pending_mode, PendingMode::Record | PendingMode::Play) {
+            let mode = std::mem::replace(&mut state.pending_mode, PendingMode::None);
+            let count = state.pending_count.take().unwrap_or(1);
+            if let Some(register) = macros::register_for_key(key) {
+                if mode == PendingMode::Record {
+                    state.recording = Some((register, Vec::new()));
+                } else {
+                    play_macro(ctx, state, config, register, count);
+                }
+            }
+            continue;
         }
-    }
 
-    if ctx.input(|i| i.key_pressed(egui::Key::P)) {
-        state.interaction_mode = toggle_mode(state.interaction_mode, InteractionMode::Paintbrush);
-        if state.interaction_mode == InteractionMode::Paintbrush {
-            state.last_paint_pos = None;
+        if state.pending_mode == PendingMode::None && modifiers.is_none() {
+            if let Some(digit) = digit_value(key) {
+                if digit > 0 || state.pending_count.is_some() {
+                    state.push_count_digit(digit);
+                    continue;
+                }
+            }
+            if key == egui::Key::Escape && state.pending_count.take().is_some() {
+                continue;
+            }
         }
-    }
 
-    if state.interaction_mode == InteractionMode::BoxSelect {
-        handle_arrow_keys(ctx, |dx, dy| {
-            state.expand_selection_box((dx, dy), config.point_radius);
-        });
-    } else if ctx.input(|i| i.key_pressed(egui::Key::S)) {
-        if state.pending_mode == PendingMode::Shape {
-            state.set_selected_shape(PointShape::Square);
-        }
-        state.pending_mode = toggle_pending(state.pending_mode, PendingMode::Shape);
-    } else if state.pending_mode == PendingMode::Shape {
-        let shape = match () {
-            () if ctx.input(|i| i.key_pressed(egui::Key::C)) => Some(PointShape::Circle),
-            () if ctx.input(|i| i.key_pressed(egui::Key::D)) => Some(PointShape::Diamond),
-            () if ctx.input(|i| i.key_pressed(egui::Key::H)) => Some(PointShape::Semicircle),
-            () => None,
-        };
-        if let Some(shape) = shape {
-            state.set_selected_shape(shape);
-            state.pending_mode = PendingMode::None;
-        }
-    } else if ctx.input(|i| i.key_pressed(egui::Key::C)) {
-        if state.pending_mode == PendingMode::Clone {
-            state.clone_selected(0.0, 0.0);
-        }
-        state.pending_mode = toggle_pending(state.pending_mode, PendingMode::Clone);
-    } else if state.pending_mode == PendingMode::Clone {
-        handle_arrow_keys(ctx, |dx, dy| {
-            let (dx, dy) = state.convex_hull_offset((dx, dy), config.point_radius);
-            state.clone_selected(dx, dy);
-            state.pending_mode = PendingMode::None;
-        });
-    } else {
-        handle_arrow_keys(ctx, |dx, dy| {
-            state.move_selected(dx * step, dy * step);
-            if state.snap_to_grid {
-                state.snap_to_grid(config.grid_spacing, config.point_radius);
+        let action = keymap.lookup(state.pending_mode, key, modifiers);
+        // Any key ends a pending chord, whether or not it completes one
+        state.pending_mode = PendingMode::None;
+        match action {
+            // The count carries over into the chord, as in `4 C ↓`
+            Some(Action::Pending(mode)) => state.pending_mode = mode,
+            Some(Action::PlayMacro) => state.pending_mode = PendingMode::Play,
+            Some(action) => {
+                let count = state.pending_count.take().unwrap_or(1);
+                run_action(ctx, state, config, action, count);
             }
-        });
+            None => state.pending_count = None,
+        }
     }
 }